@@ -49,9 +49,21 @@ StandardError=journal
 WantedBy=graphical.target
 EOF
 
-# Install unclutter for mouse hiding
+# Let the kiosk read the Jabra's buttons from /dev/hidraw*, keyboards / remotes from /dev/input
+# and GPIO buttons from /dev/gpiochip* (the gpio group only exists on Raspberry Pi OS)
+cat > /etc/udev/rules.d/70-rctv-hidraw.rules << EOF
+KERNEL=="hidraw*", SUBSYSTEM=="hidraw", GROUP="plugdev", MODE="0660"
+EOF
+usermod -aG plugdev,input "$ACTUAL_USER"
+if getent group gpio > /dev/null; then
+    usermod -aG gpio "$ACTUAL_USER"
+fi
+udevadm control --reload-rules
+udevadm trigger --subsystem-match=hidraw
+
+# Install unclutter for mouse hiding, and imagemagick for screenshots
 apt-get update
-apt-get install -y unclutter
+apt-get install -y unclutter imagemagick
 
 # Create mouse hiding service
 cat > /etc/systemd/system/hide-mouse.service << EOF
@@ -0,0 +1,761 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+// Optional settings file, read next to the token file. Every field has a default,
+// so a missing file (or a partial one) is fine.
+pub const DEFAULT_CONFIG_PATH: &str = "/home/rctv/.rctvconfig.json";
+pub const TOKEN_PATH: &str = "/home/rctv/.rctvtoken"; // When --token isn't given
+
+// Cap on the meeting length settings, which end up added to instants. A week is plenty.
+const MAX_MEETING_MINUTES: u64 = 7 * 24 * 60;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(alias = "zoom")]
+    pub meeting: MeetingSettings,
+    pub meetings: Vec<MeetingConfig>, // toggle_meeting joins the first one, join_meeting picks one
+    pub diagnostics: DiagnosticsConfig,
+    pub browser: BrowserConfig,
+    pub hid: HidConfig,
+    pub gestures: GestureConfig,
+    pub evdev: EvdevConfig,
+    pub gpio: GpioConfig,
+    pub control_api: ControlApiConfig,
+    pub logging: LoggingConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub screenshots: ScreenshotConfig,
+    pub updater: UpdaterConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            meeting: MeetingSettings::default(),
+            meetings: vec![MeetingConfig::default()],
+            diagnostics: DiagnosticsConfig::default(),
+            browser: BrowserConfig::default(),
+            hid: HidConfig::default(),
+            gestures: GestureConfig::default(),
+            evdev: EvdevConfig::default(),
+            gpio: GpioConfig::default(),
+            control_api: ControlApiConfig::default(),
+            logging: LoggingConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            screenshots: ScreenshotConfig::default(),
+            updater: UpdaterConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Zoom,
+    Jitsi,
+    Meet,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MeetingConfig {
+    pub name: String,
+    pub provider: ProviderKind,
+    pub url: String,
+    pub display_name: String, // Name shown to others, for providers that ask for one (Jitsi)
+    pub policy: MeetingPolicy,
+}
+
+// How the TV should be set up in the meeting. Applied once joined, and again whenever the
+// meeting drifts from it (e.g. Zoom turning the camera back on). Unset means leave it alone.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MeetingPolicy {
+    pub mic_muted: Option<bool>, // Microphone muted
+    pub camera_on: Option<bool>, // Camera on
+    pub speaker_view: bool,      // Speaker view rather than gallery / tile view (Zoom, Jitsi)
+    pub hide_self_view: bool,    // Don't show the TV's own camera (Zoom, Jitsi)
+    pub full_screen: bool,       // Make the browser window full screen
+}
+
+impl Default for MeetingConfig {
+    fn default() -> Self {
+        MeetingConfig {
+            name: "rctv".to_string(),
+            provider: ProviderKind::Zoom,
+            url: "https://app.zoom.us/wc/2125949362/join?fromPWA=1&pwd=OEJ3Nkw4djlmSlBBVWl2aVdXTk93Zz09".to_string(),
+            display_name: "RCTV".to_string(),
+            policy: MeetingPolicy::default(),
+        }
+    }
+}
+
+// Applies to every meeting, whatever the provider
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MeetingSettings {
+    pub step_timeout_seconds: u64,    // Max time for a single join step (e.g. finding the Join button)
+    pub overall_timeout_seconds: u64, // Max time from launching Chromium to being in the meeting
+    pub max_meeting_minutes: u64,     // Hang up automatically after this long in the meeting
+    pub warning_minutes: u64,         // Show a warning on screen this long before hanging up
+    pub extension_minutes: u64,       // Pressing the button during the warning adds this much time (0 disables)
+    pub camera_timeout_seconds: u64,  // Max wait for the camera preview before joining (joins anyway after)
+    pub join_confirm_seconds: u64,    // After clicking Join, how long to wait to be in the meeting before clicking again
+}
+
+impl Default for MeetingSettings {
+    fn default() -> Self {
+        MeetingSettings {
+            step_timeout_seconds: 60,
+            overall_timeout_seconds: 5 * 60,
+            max_meeting_minutes: 60,
+            warning_minutes: 5,
+            extension_minutes: 30,
+            camera_timeout_seconds: 15,
+            join_confirm_seconds: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    pub dir: String,          // Where screenshots/page sources of failed automation steps go
+    pub max_snapshots: usize, // Oldest snapshots are deleted past this many (0 disables snapshots)
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        DiagnosticsConfig {
+            dir: "/home/rctv/.rctv-diagnostics".to_string(),
+            max_snapshots: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrowserBackend {
+    #[default]
+    Cdp,       // Chrome DevTools protocol, straight to Chromium's debugging port
+    WebDriver, // Through ChromeDriver (needs the chromium-chromedriver package)
+    Webview,   // In a second Tauri window instead of Chromium (falls back to Chromium over DevTools)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BrowserConfig {
+    pub backend: BrowserBackend,      // ChromeDriver is also used if the DevTools connection fails
+    pub debugging_port: u16,          // Chromium remote debugging port (0 picks a free one)
+    pub chromedriver_port: u16,       // ChromeDriver port, for the webdriver backend (0 picks a free one)
+    pub startup_timeout_seconds: u64, // Max wait for Chromium's debugging port / ChromeDriver to be ready
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        BrowserConfig {
+            backend: BrowserBackend::default(),
+            debugging_port: 0,
+            chromedriver_port: 0,
+            startup_timeout_seconds: 30,
+        }
+    }
+}
+
+// What a button (or any other input) can do
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    ToggleMeeting, // Join the meeting, or leave it (extend it during the end of meeting warning)
+    Mute,          // Toggle the microphone while in the meeting
+    VolumeUp,
+    VolumeDown,
+    NextSlide,     // Skip to the next kiosk URL
+    PrevSlide,     // Back to the previous kiosk URL
+    Hangup,        // Leave the meeting
+    JoinMeeting(usize), // Like toggle_meeting, for another of `meetings`: {"join_meeting": 1} joins the second one
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HidConfig {
+    pub devices: Vec<DeviceMatch>,   // Which hidraw device to listen to, the first rule that matches a device wins
+    pub buttons: Vec<ButtonMapping>, // First matching pattern wins
+    pub recording: Option<String>,   // Read reports from a hid-recorder recording (file or pipe) instead of the device, for testing
+}
+
+impl Default for HidConfig {
+    fn default() -> Self {
+        HidConfig {
+            devices: vec![DeviceMatch { name: Some("Jabra".to_string()), ..DeviceMatch::default() }],
+            buttons: vec![ButtonMapping {
+                report: "3 03 01 00".to_string(),
+                release: None,
+                action: Action::ToggleMeeting,
+                long_press: Some(Action::Hangup),
+                double_press: None,
+            }],
+            recording: None,
+        }
+    }
+}
+
+// Every field that is set has to match
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeviceMatch {
+    pub id: Option<String>,       // "vendor:product" in hex, as lsusb shows it, e.g. "0b0e:0422"
+    pub name: Option<String>,     // Regex on the device name
+    pub serial: Option<String>,
+    pub usb_path: Option<String>, // Start of where the device is plugged in (HID_PHYS), e.g. "usb-0000:01:00.0-1.3"
+}
+
+impl DeviceMatch {
+    pub fn vendor_product(&self) -> Option<(u16, u16)> {
+        let (vendor, product) = self.id.as_deref()?.split_once(':')?;
+        Some((u16::from_str_radix(vendor, 16).ok()?, u16::from_str_radix(product, 16).ok()?))
+    }
+
+    pub fn name_regex(&self) -> Option<Result<regex::Regex, regex::Error>> {
+        self.name.as_deref().map(regex::Regex::new)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_none() && self.name.is_none() && self.serial.is_none() && self.usb_path.is_none() {
+            return Err("a device rule needs at least one of id, name, serial or usb_path".to_string());
+        }
+        if let Some(id) = &self.id {
+            if self.vendor_product().is_none() {
+                return Err(format!("`{}` is not a vendor:product id (e.g. 0b0e:0422)", id));
+            }
+        }
+        if let Some(Err(e)) = self.name_regex() {
+            return Err(format!("invalid device name regex: {}", e));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ButtonMapping {
+    pub report: String,               // Report as hid-recorder prints it: length, then bytes in hex. `xx` matches any byte
+    pub release: Option<String>,      // Report sent when the button is let go, for devices that send one
+    pub action: Action,               // On a short press
+    pub long_press: Option<Action>,
+    pub double_press: Option<Action>,
+}
+
+impl ButtonMapping {
+    pub fn is_press(&self, report: &str) -> bool {
+        report_matches(&self.report, report)
+    }
+
+    pub fn is_release(&self, report: &str) -> bool {
+        self.release.as_deref().is_some_and(|release| report_matches(release, report))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_report_pattern(&self.report)?;
+        if let Some(release) = &self.release {
+            validate_report_pattern(release)?;
+        }
+        Ok(())
+    }
+}
+
+// Whether a report, formatted like hid-recorder does (`<length> <bytes...>`), starts with `pattern`
+fn report_matches(pattern: &str, report: &str) -> bool {
+    let mut received = report.split_whitespace();
+    pattern.split_whitespace().all(|expected| match received.next() {
+        Some(byte) => expected.eq_ignore_ascii_case("xx") || expected.eq_ignore_ascii_case(byte),
+        None => false,
+    })
+}
+
+fn validate_report_pattern(pattern: &str) -> Result<(), String> {
+    let valid = |token: &str| token.eq_ignore_ascii_case("xx") || u8::from_str_radix(token, 16).is_ok();
+    if pattern.split_whitespace().next().is_none() || !pattern.split_whitespace().all(valid) {
+        return Err(format!("`{}` is not a HID report pattern (hex bytes or xx, separated by spaces)", pattern));
+    }
+    Ok(())
+}
+
+// Keyboards and USB presenter remotes, through /dev/input/event*
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EvdevConfig {
+    pub devices: Vec<DeviceMatch>, // Every device matching one of these is used (none disables keyboard input)
+    pub keys: Vec<KeyMapping>,
+    pub grab: bool,                // Keep the keys from also reaching the kiosk page
+}
+
+impl Default for EvdevConfig {
+    fn default() -> Self {
+        let key = |key: &str, action, long_press| KeyMapping { key: key.to_string(), action, long_press, double_press: None };
+        EvdevConfig {
+            devices: Vec::new(),
+            keys: vec![
+                key("KEY_B", Action::ToggleMeeting, Some(Action::Hangup)),
+                key("KEY_ESC", Action::Hangup, None),
+                key("KEY_PAGEDOWN", Action::NextSlide, None),
+                key("KEY_PAGEUP", Action::PrevSlide, None),
+            ],
+            grab: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyMapping {
+    pub key: String, // e.g. "KEY_PAGEDOWN", or a key code
+    pub action: Action,
+    pub long_press: Option<Action>,
+    pub double_press: Option<Action>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpioEdge {
+    #[default]
+    Falling, // Button between the pin and ground, with the pull-up
+    Rising,  // Button between the pin and 3.3V, with the pull-down
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpioBias {
+    #[default]
+    PullUp,
+    PullDown,
+    Disabled, // External resistor
+    AsIs,     // Leave whatever the chip is set to
+}
+
+// A push button wired to a GPIO pin, through the GPIO character device (/dev/gpiochipN)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GpioConfig {
+    pub chip: String,        // "/dev/gpiochip0", or just "gpiochip0" (gpiochip4 on a Pi 5 with an older kernel)
+    pub line: Option<u32>,   // Line offset on the chip, the BCM GPIO number on a Pi (unset disables GPIO input)
+    pub edge: GpioEdge,      // Edge of a press
+    pub release: bool,       // Also watch the other edge as the release, needed to tell how long the button is held
+    pub bias: GpioBias,
+    pub debounce_ms: u64,    // Contact bounce filtered by the kernel (0 disables it)
+    pub action: Action,
+    pub long_press: Option<Action>,
+    pub double_press: Option<Action>,
+}
+
+impl Default for GpioConfig {
+    fn default() -> Self {
+        GpioConfig {
+            chip: "/dev/gpiochip0".to_string(),
+            line: None,
+            edge: GpioEdge::default(),
+            release: true,
+            bias: GpioBias::default(),
+            debounce_ms: 20,
+            action: Action::ToggleMeeting,
+            long_press: Some(Action::Hangup),
+            double_press: None,
+        }
+    }
+}
+
+// The local HTTP API to control the TV without its buttons
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ControlApiConfig {
+    pub enabled: bool,
+    pub bind: String,          // Address and port to listen on, e.g. "0.0.0.0:8350" to reach it from the network
+    pub token: Option<String>, // Bearer token requests need, the TV token if unset
+}
+
+impl Default for ControlApiConfig {
+    fn default() -> Self {
+        ControlApiConfig {
+            enabled: true,
+            bind: "127.0.0.1:8350".to_string(),
+            token: None,
+        }
+    }
+}
+
+// Log levels and where logs go, on top of stdout (the journal)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub filter: String,   // Level, per target if needed (api, kiosk, zoom, hid, process), e.g. "info,zoom=debug"
+    pub json: bool,       // One JSON object per line instead of text, for log collectors
+    pub file: bool,       // Also log to a file in the app's log directory, rotated daily
+    pub max_files: usize, // Daily log files to keep
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            filter: "info".to_string(),
+            json: false,
+            file: true,
+            max_files: 7,
+        }
+    }
+}
+
+// Regular check-ins with the RCTV server, so a misbehaving TV shows up without SSHing in
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    pub url: Option<String>,     // Where to post heartbeats, with the TV token (unset disables them)
+    pub interval_seconds: u64,
+    pub ship_logs: bool,         // Send the warnings and errors logged since the last heartbeat, not just how many
+    pub buffer_file: String,     // Heartbeats that couldn't be sent wait here until the server is reachable
+    pub max_buffered: usize,     // The oldest buffered heartbeats are dropped past this many
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            url: None,
+            interval_seconds: 60,
+            ship_logs: true,
+            buffer_file: "/home/rctv/.rctv-heartbeats.jsonl".to_string(),
+            max_buffered: 1440,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds)
+    }
+}
+
+// What's on screen, for a preview in the RCTV web app or from the control API
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    pub max_width: u32,          // Screenshots are scaled down to fit, keeping the aspect ratio
+    pub max_height: u32,
+    pub quality: u8,             // JPEG quality, 1 to 100
+    pub upload_url: Option<String>, // Where to post them, with the TV token (unset keeps them for the control API only)
+    pub in_meetings: bool,       // Also take them during meetings, of the whole display
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            enabled: false,
+            interval_seconds: 60,
+            max_width: 960,
+            max_height: 540,
+            quality: 70,
+            upload_url: None,
+            in_meetings: false,
+        }
+    }
+}
+
+impl ScreenshotConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds)
+    }
+}
+
+// Updates from the endpoint in tauri.conf.json, signed with the key there
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UpdaterConfig {
+    pub enabled: bool,
+    pub interval_hours: u64,
+    pub first_check_minutes: u64, // After startup, so a bad network at boot doesn't count
+    pub install: bool,            // Install updates that are found, not just report them. Only possible for the AppImage
+}
+
+impl Default for UpdaterConfig {
+    fn default() -> Self {
+        UpdaterConfig {
+            enabled: true,
+            interval_hours: 12,
+            first_check_minutes: 5,
+            install: true,
+        }
+    }
+}
+
+impl UpdaterConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_hours * 3600)
+    }
+
+    pub fn first_check(&self) -> Duration {
+        Duration::from_secs(self.first_check_minutes * 60)
+    }
+}
+
+// Telling short, long and double presses apart
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GestureConfig {
+    pub debounce_ms: u64,     // Repeated reports and contact bounce within this are the same press
+    pub long_press_ms: u64,   // Held at least this long is a long press
+    pub double_press_ms: u64, // A second press within this after the first is a double press
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        GestureConfig {
+            debounce_ms: 300,
+            long_press_ms: 1500,
+            double_press_ms: 400,
+        }
+    }
+}
+
+impl GestureConfig {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+
+    pub fn long_press(&self) -> Duration {
+        Duration::from_millis(self.long_press_ms)
+    }
+
+    pub fn double_press(&self) -> Duration {
+        Duration::from_millis(self.double_press_ms)
+    }
+}
+
+impl BrowserConfig {
+    pub fn startup_timeout(&self) -> Duration {
+        Duration::from_secs(self.startup_timeout_seconds)
+    }
+}
+
+impl MeetingSettings {
+    pub fn step_timeout(&self) -> Duration {
+        Duration::from_secs(self.step_timeout_seconds)
+    }
+
+    pub fn overall_timeout(&self) -> Duration {
+        Duration::from_secs(self.overall_timeout_seconds)
+    }
+
+    pub fn max_meeting_length(&self) -> Duration {
+        Duration::from_secs(self.max_meeting_minutes * 60)
+    }
+
+    pub fn warning_period(&self) -> Duration {
+        Duration::from_secs(self.warning_minutes * 60)
+    }
+
+    pub fn extension(&self) -> Duration {
+        Duration::from_secs(self.extension_minutes * 60)
+    }
+
+    pub fn camera_timeout(&self) -> Duration {
+        Duration::from_secs(self.camera_timeout_seconds)
+    }
+
+    pub fn join_confirm_timeout(&self) -> Duration {
+        Duration::from_secs(self.join_confirm_seconds)
+    }
+}
+
+impl Config {
+    // `path` is the --config argument, if given. An explicit path has to exist;
+    // the default path is only used when present.
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (DEFAULT_CONFIG_PATH, false),
+        };
+
+        let config: Config = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Invalid config file {}: {}", path, e))?,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("Could not read config file {}: {}", path, e)),
+        };
+
+        if config.meetings.is_empty() {
+            return Err(format!("Invalid config file {}: `meetings` needs at least one meeting", path));
+        }
+        for (name, minutes) in [
+            ("max_meeting_minutes", config.meeting.max_meeting_minutes),
+            ("warning_minutes", config.meeting.warning_minutes),
+            ("extension_minutes", config.meeting.extension_minutes),
+        ] {
+            if minutes > MAX_MEETING_MINUTES {
+                return Err(format!("Invalid config file {}: `meeting.{}` can't be more than {}", path, name, MAX_MEETING_MINUTES));
+            }
+        }
+        // The control API picks meetings by name
+        if let Some((_, meeting)) = config.meetings.iter().enumerate().find(|(i, meeting)| config.meetings[..*i].iter().any(|other| other.name == meeting.name)) {
+            return Err(format!("Invalid config file {}: two meetings are named `{}`", path, meeting.name));
+        }
+        let actions = config.hid.buttons.iter().map(|button| (button.action, button.long_press, button.double_press))
+            .chain(config.evdev.keys.iter().map(|key| (key.action, key.long_press, key.double_press)))
+            .chain([(config.gpio.action, config.gpio.long_press, config.gpio.double_press)])
+            .flat_map(|(action, long_press, double_press)| [Some(action), long_press, double_press])
+            .flatten();
+        for action in actions {
+            if let Action::JoinMeeting(index) = action {
+                if index >= config.meetings.len() {
+                    return Err(format!("Invalid config file {}: `join_meeting` {} doesn't exist, there are {} meetings", path, index, config.meetings.len()));
+                }
+            }
+        }
+        for device in &config.hid.devices {
+            device.validate().map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+        }
+        for button in &config.hid.buttons {
+            button.validate().map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+        }
+        for device in &config.evdev.devices {
+            device.validate().map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+        }
+        if let Some(key) = config.evdev.keys.iter().find(|key| crate::input::key_code(&key.key).is_none()) {
+            return Err(format!("Invalid config file {}: unknown key `{}`", path, key.key));
+        }
+        if let Err(e) = config.control_api.bind.parse::<std::net::SocketAddr>() {
+            return Err(format!("Invalid config file {}: `control_api.bind`: {}", path, e));
+        }
+        if config.control_api.token.as_ref().is_some_and(|token| token.is_empty()) {
+            return Err(format!("Invalid config file {}: `control_api.token` is empty", path));
+        }
+        if let Some(url) = &config.heartbeat.url {
+            url::Url::parse(url).map_err(|e| format!("Invalid config file {}: `heartbeat.url`: {}", path, e))?;
+        }
+        if config.heartbeat.interval_seconds == 0 {
+            return Err(format!("Invalid config file {}: `heartbeat.interval_seconds` can't be 0", path));
+        }
+        if let Some(url) = &config.screenshots.upload_url {
+            url::Url::parse(url).map_err(|e| format!("Invalid config file {}: `screenshots.upload_url`: {}", path, e))?;
+        }
+        if config.screenshots.interval_seconds == 0 {
+            return Err(format!("Invalid config file {}: `screenshots.interval_seconds` can't be 0", path));
+        }
+        if !(1..=100).contains(&config.screenshots.quality) {
+            return Err(format!("Invalid config file {}: `screenshots.quality` has to be between 1 and 100", path));
+        }
+        if config.updater.interval_hours == 0 {
+            return Err(format!("Invalid config file {}: `updater.interval_hours` can't be 0", path));
+        }
+        // Turned into seconds by UpdaterConfig
+        if config.updater.interval_hours.checked_mul(3600).is_none() {
+            return Err(format!("Invalid config file {}: `updater.interval_hours` is too long", path));
+        }
+        if config.updater.first_check_minutes.checked_mul(60).is_none() {
+            return Err(format!("Invalid config file {}: `updater.first_check_minutes` is too long", path));
+        }
+        crate::logging::check_filter(&config.logging.filter).map_err(|e| format!("Invalid config file {}: `logging.filter`: {}", path, e))?;
+        // The kernel takes the debounce period in microseconds, as 32 bits
+        if config.gpio.debounce_ms > u64::from(u32::MAX) / 1000 {
+            return Err(format!("Invalid config file {}: `gpio.debounce_ms` is too long", path));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_reports_against_patterns() {
+        // The Jabra's hook switch, as hid-recorder prints it
+        let report = "3 03 01 00";
+        assert!(report_matches("3 03 01 00", report));
+        assert!(report_matches("3 03 XX 00", report));
+        assert!(report_matches("3 xx xx xx", report));
+        // A pattern only has to match the start
+        assert!(report_matches("3 03 01", report));
+        assert!(!report_matches("3 03 02 00", report));
+        // The length is part of the pattern
+        assert!(!report_matches("4 03 01 00", report));
+        assert!(!report_matches("2 03 01", report));
+        assert!(!report_matches("3 03 01 00 00", report));
+        assert!(report_matches("8 02 ab", "8 02 ab 00 00 00 00 00 00"));
+    }
+
+    #[test]
+    fn validates_report_patterns() {
+        assert!(validate_report_pattern("3 03 01 00").is_ok());
+        assert!(validate_report_pattern("3 xx XX 0f").is_ok());
+        assert!(validate_report_pattern("").is_err());
+        assert!(validate_report_pattern("   ").is_err());
+        assert!(validate_report_pattern("3 03 zz").is_err());
+        assert!(validate_report_pattern("3 03 100").is_err());
+        assert!(validate_report_pattern("E: 3 03 01 00").is_err());
+    }
+
+    #[test]
+    fn reads_actions() {
+        let action = |json: &str| serde_json::from_str::<Action>(json).ok();
+        assert_eq!(action(r#""toggle_meeting""#), Some(Action::ToggleMeeting));
+        assert_eq!(action(r#"{"join_meeting": 1}"#), Some(Action::JoinMeeting(1)));
+        assert_eq!(action(r#""join_meeting""#), None);
+        assert_eq!(action(r#"{"join_meeting": -1}"#), None);
+    }
+
+    // Config::load on `json`, written to a file of its own
+    fn load(name: &str, json: &str) -> Result<Config, String> {
+        let path = std::env::temp_dir().join(format!("rctv-config-test-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, json).unwrap();
+        let config = Config::load(Some(path.to_str().unwrap()));
+        let _ = std::fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn checks_meetings_joined_by_buttons() {
+        let load = |json: &str| load("meetings", json).map(|_| ());
+        let meetings = r#""meetings": [{"name": "Hub"}, {"name": "Lounge"}]"#;
+        assert!(load(&format!(r#"{{{}, "gpio": {{"action": {{"join_meeting": 1}}}}}}"#, meetings)).is_ok());
+        let error = load(&format!(r#"{{{}, "evdev": {{"keys": [{{"key": "KEY_A", "action": "mute", "long_press": {{"join_meeting": 2}}}}]}}}}"#, meetings)).unwrap_err();
+        assert!(error.contains("`join_meeting` 2 doesn't exist"), "{}", error);
+        let error = load(r#"{"meetings": [{"name": "Hub"}, {"name": "Hub"}]}"#).unwrap_err();
+        assert!(error.contains("two meetings are named `Hub`"), "{}", error);
+    }
+
+    #[test]
+    fn caps_meeting_lengths() {
+        let config = load("meeting-lengths", r#"{"meeting": {"max_meeting_minutes": 10080, "extension_minutes": 10080}}"#).unwrap();
+        assert_eq!(config.meeting.max_meeting_length(), Duration::from_secs(7 * 24 * 3600));
+        for setting in ["max_meeting_minutes", "warning_minutes", "extension_minutes"] {
+            let error = load("meeting-lengths", &format!(r#"{{"meeting": {{"{}": 10081}}}}"#, setting)).unwrap_err();
+            assert!(error.contains(&format!("`meeting.{}` can't be more than 10080", setting)), "{}", error);
+            // Would overflow when turned into seconds
+            assert!(load("meeting-lengths", &format!(r#"{{"meeting": {{"{}": {}}}}}"#, setting, u64::MAX)).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_update_intervals_too_long_for_seconds() {
+        let config = load("updater", r#"{"updater": {"interval_hours": 5124095576030431, "first_check_minutes": 307445734561825860}}"#).unwrap();
+        assert_eq!(config.updater.interval().as_secs(), 5124095576030431 * 3600);
+        assert_eq!(config.updater.first_check().as_secs(), 307445734561825860 * 60);
+        let error = load("updater", r#"{"updater": {"interval_hours": 5124095576030432}}"#).unwrap_err();
+        assert!(error.contains("`updater.interval_hours` is too long"), "{}", error);
+        let error = load("updater", r#"{"updater": {"first_check_minutes": 307445734561825861}}"#).unwrap_err();
+        assert!(error.contains("`updater.first_check_minutes` is too long"), "{}", error);
+    }
+
+    #[test]
+    fn tells_presses_from_releases() {
+        let button = ButtonMapping {
+            report: "3 03 01 xx".to_string(),
+            release: Some("3 03 00".to_string()),
+            action: Action::ToggleMeeting,
+            long_press: None,
+            double_press: None,
+        };
+        assert!(button.is_press("3 03 01 00"));
+        assert!(!button.is_release("3 03 01 00"));
+        assert!(button.is_release("3 03 00 00"));
+        assert!(!button.is_press("3 03 00 00"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+use url::Url;
+use tracing::info;
+
+use crate::config::Action;
+use crate::controls::{Controller, KioskControl, KioskRequest, MAX_SHOW_DURATION};
+use crate::logging;
+use crate::metrics;
+use crate::screenshot;
+use crate::status::StatusReport;
+use crate::AutomationState;
+
+// How long /show keeps a URL up when the request doesn't say
+const DEFAULT_SHOW_SECONDS: u64 = 60;
+
+struct ApiState {
+    controller: Controller,
+    token: String,
+}
+
+type SharedState = State<Arc<ApiState>>;
+
+// Failures are the status code and a plain text reason
+type ApiError = (StatusCode, String);
+
+#[derive(Serialize, Deserialize)]
+struct StateBody {
+    state: AutomationState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LoggingBody {
+    filter: String,
+}
+
+#[derive(Deserialize)]
+struct StartMeetingQuery {
+    meeting: Option<String>, // Name of one of `meetings`, the first one if not given
+}
+
+#[derive(Deserialize)]
+struct ShowBody {
+    url: String,
+    seconds: Option<u64>,
+}
+
+async fn status(controller: &Controller) -> Json<StatusReport> {
+    Json(StatusReport::collect(controller).await)
+}
+
+fn require_state(controller: &Controller, expected: AutomationState) -> Result<(), ApiError> {
+    let current = *controller.state.lock().unwrap();
+    if current != expected {
+        return Err((StatusCode::CONFLICT, format!("Not possible in state {:?}", current)));
+    }
+    Ok(())
+}
+
+// Starting and stopping meetings do nothing if another input changed the state first
+fn require_changed(controller: &Controller, changed: bool) -> Result<(), ApiError> {
+    if !changed {
+        return Err((StatusCode::CONFLICT, format!("Not possible in state {:?}", *controller.state.lock().unwrap())));
+    }
+    Ok(())
+}
+
+fn require_playlist(controller: &Controller) -> Result<(), ApiError> {
+    if !controller.app_handle.state::<KioskControl>().has_playlist() {
+        return Err((StatusCode::CONFLICT, "No playlist to go through".to_string()));
+    }
+    Ok(())
+}
+
+// Compares in constant time, so the token can't be guessed byte by byte from response times
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn check_token(State(api): SharedState, request: Request, next: Next) -> Response {
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !given.is_some_and(|given| token_matches(given, &api.token)) {
+        return (StatusCode::UNAUTHORIZED, "Missing or wrong bearer token").into_response();
+    }
+    next.run(request).await
+}
+
+async fn get_status(State(api): SharedState) -> Json<StatusReport> {
+    status(&api.controller).await
+}
+
+async fn get_metrics() -> ([(header::HeaderName, &'static str); 1], String) {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
+}
+
+// The last screenshot, as a JPEG. None during meetings unless `screenshots.in_meetings` is set.
+async fn get_screenshot() -> Result<Response, ApiError> {
+    screenshot::with_latest(|latest| {
+        let latest = latest.ok_or((StatusCode::NOT_FOUND, "No screenshot yet, or in a meeting".to_string()))?;
+        let headers = [
+            (header::CONTENT_TYPE, "image/jpeg".to_string()),
+            (header::HeaderName::from_static("x-taken-at"), latest.taken_at.to_string()),
+        ];
+        Ok((headers, latest.jpeg.clone()).into_response())
+    })
+}
+
+async fn get_state(State(api): SharedState) -> Json<StateBody> {
+    Json(StateBody { state: *api.controller.state.lock().unwrap() })
+}
+
+// Only the states a button could get to: kiosk_mode leaves the meeting, zoom_running joins it
+async fn set_state(State(api): SharedState, Json(body): Json<StateBody>) -> Result<Json<StatusReport>, ApiError> {
+    let current = *api.controller.state.lock().unwrap();
+    let changed = match (body.state, current) {
+        (target, current) if target == current => true,
+        (AutomationState::KioskMode, AutomationState::ZoomComplete) => api.controller.stop_meeting(),
+        (AutomationState::ZoomRunning, AutomationState::KioskMode) => api.controller.start_meeting(0),
+        (AutomationState::KioskMode | AutomationState::ZoomRunning, current) => {
+            return Err((StatusCode::CONFLICT, format!("Can't go to {:?} from {:?}", body.state, current)));
+        }
+        (target, _) => return Err((StatusCode::BAD_REQUEST, format!("{:?} can't be set, use kiosk_mode or zoom_running", target))),
+    };
+    require_changed(&api.controller, changed)?;
+    Ok(status(&api.controller).await)
+}
+
+async fn get_logging() -> Json<LoggingBody> {
+    Json(LoggingBody { filter: logging::current_filter().unwrap_or_default() })
+}
+
+// e.g. {"filter": "info,zoom=debug"} to see what the join automation is doing
+async fn set_logging(Json(body): Json<LoggingBody>) -> Result<Json<LoggingBody>, ApiError> {
+    logging::set_filter(&body.filter).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    info!(target: "api", "Control API: log filter set to {}", body.filter);
+    Ok(get_logging().await)
+}
+
+async fn next_slide(State(api): SharedState) -> Result<Json<StatusReport>, ApiError> {
+    require_state(&api.controller, AutomationState::KioskMode)?;
+    require_playlist(&api.controller)?;
+    api.controller.handle(Action::NextSlide);
+    Ok(status(&api.controller).await)
+}
+
+async fn previous_slide(State(api): SharedState) -> Result<Json<StatusReport>, ApiError> {
+    require_state(&api.controller, AutomationState::KioskMode)?;
+    require_playlist(&api.controller)?;
+    api.controller.handle(Action::PrevSlide);
+    Ok(status(&api.controller).await)
+}
+
+async fn show_url(State(api): SharedState, Json(body): Json<ShowBody>) -> Result<Json<StatusReport>, ApiError> {
+    match Url::parse(&body.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        Ok(_) => return Err((StatusCode::BAD_REQUEST, "Only http and https URLs can be shown".to_string())),
+        Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Invalid URL: {}", e))),
+    }
+    let duration = Duration::from_secs(body.seconds.unwrap_or(DEFAULT_SHOW_SECONDS));
+    if duration > MAX_SHOW_DURATION {
+        return Err((StatusCode::BAD_REQUEST, format!("`seconds` can't be more than {}", MAX_SHOW_DURATION.as_secs())));
+    }
+    require_state(&api.controller, AutomationState::KioskMode)?;
+    info!(target: "api", "Control API: showing {} for {} seconds", body.url, duration.as_secs());
+    api.controller.app_handle.state::<KioskControl>().request(KioskRequest::Show { url: body.url, duration });
+    Ok(status(&api.controller).await)
+}
+
+async fn start_meeting(State(api): SharedState, Query(query): Query<StartMeetingQuery>) -> Result<Json<StatusReport>, ApiError> {
+    let meeting = match &query.meeting {
+        Some(name) => api.controller.config.meetings.iter().position(|meeting| &meeting.name == name)
+            .ok_or((StatusCode::NOT_FOUND, format!("No meeting named {}", name)))?,
+        None => 0,
+    };
+    require_state(&api.controller, AutomationState::KioskMode)?;
+    require_changed(&api.controller, api.controller.start_meeting(meeting))?;
+    Ok(status(&api.controller).await)
+}
+
+async fn stop_meeting(State(api): SharedState) -> Result<Json<StatusReport>, ApiError> {
+    require_state(&api.controller, AutomationState::ZoomComplete)?;
+    require_changed(&api.controller, api.controller.stop_meeting())?;
+    Ok(status(&api.controller).await)
+}
+
+async fn reload_playlist(State(api): SharedState) -> Json<StatusReport> {
+    info!(target: "api", "Control API: reloading the playlist");
+    api.controller.app_handle.state::<KioskControl>().request(KioskRequest::Reload);
+    status(&api.controller).await
+}
+
+// Serves the control API on `control_api.bind` for as long as the app runs. Everything needs
+// `Authorization: Bearer <token>`.
+pub async fn start_control_api(controller: Controller, token: String) -> Result<(), String> {
+    if token.is_empty() {
+        return Err("no token to protect it with".to_string());
+    }
+    let bind = controller.config.control_api.bind.clone();
+    let api = Arc::new(ApiState { controller, token });
+
+    let router = Router::new()
+        .route("/status", get(get_status))
+        .route("/metrics", get(get_metrics))
+        .route("/screenshot", get(get_screenshot))
+        .route("/logging", get(get_logging).put(set_logging))
+        .route("/state", get(get_state).put(set_state))
+        .route("/slides/next", post(next_slide))
+        .route("/slides/previous", post(previous_slide))
+        .route("/show", post(show_url))
+        .route("/meeting/start", post(start_meeting))
+        .route("/meeting/stop", post(stop_meeting))
+        .route("/playlist/reload", post(reload_playlist))
+        .layer(middleware::from_fn_with_state(Arc::clone(&api), check_token))
+        .with_state(api);
+
+    let listener = tokio::net::TcpListener::bind(&bind).await.map_err(|e| format!("could not listen on {}: {}", bind, e))?;
+    info!(target: "api", "Control API listening on http://{}", bind);
+    axum::serve(listener, router).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,262 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+use crate::config::{Action, Config};
+use crate::input::HidConnection;
+use crate::meeting::{self, kill_chrome_processes, MeetingClock, MeetingSession};
+use crate::metrics;
+use crate::{return_to_kiosk_mode, AutomationState};
+
+// How much one volume button press changes the default output
+const VOLUME_STEP: &str = "5%";
+
+// The longest a URL stays on screen, whoever asks: the playlist or the control API
+pub const MAX_SHOW_DURATION: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KioskRequest {
+    Next,
+    Previous,
+    Show { url: String, duration: Duration }, // Show a URL for a while, then carry on with the playlist
+    Reload,                                   // Fetch the playlist again now
+}
+
+// Lets buttons and the control API steer the kiosk loop. Managed as Tauri state, so the kiosk
+// loop can get at it wherever it was started from.
+#[derive(Default)]
+pub struct KioskControl {
+    pending: Mutex<Option<KioskRequest>>,
+    notify: tokio::sync::Notify,
+    has_playlist: AtomicBool, // Whether the loop has URLs to go through, set after each fetch
+}
+
+impl KioskControl {
+    // A newer request replaces one the loop hasn't picked up yet
+    pub fn request(&self, request: KioskRequest) {
+        *self.pending.lock().unwrap() = Some(request);
+        self.notify.notify_one();
+    }
+
+    pub fn set_has_playlist(&self, has_playlist: bool) {
+        self.has_playlist.store(has_playlist, Ordering::SeqCst);
+    }
+
+    // Changing slides is only possible with a playlist
+    pub fn has_playlist(&self) -> bool {
+        self.has_playlist.load(Ordering::SeqCst)
+    }
+
+    // Waits up to `timeout`, returning early if something is requested in the meantime
+    pub async fn wait(&self, timeout: Duration) -> Option<KioskRequest> {
+        if let Some(request) = self.pending.lock().unwrap().take() {
+            return Some(request);
+        }
+        let _ = tokio::time::timeout(timeout, self.notify.notified()).await;
+        self.pending.lock().unwrap().take()
+    }
+}
+
+// Everything an input needs to act on the TV. Cheap to clone, the state is shared.
+#[derive(Clone)]
+pub struct Controller {
+    pub token: String,
+    pub config: Arc<Config>,
+    pub app_handle: Arc<tauri::AppHandle>,
+    pub state: Arc<Mutex<AutomationState>>,
+    pub meeting_clock: Arc<Mutex<MeetingClock>>,
+    pub current_meeting: Arc<Mutex<Option<Arc<MeetingSession>>>>, // Set while we're in a meeting
+    pub hid_connection: Arc<Mutex<HidConnection>>,
+}
+
+impl Controller {
+    // Has to be called from within a Tokio runtime, the slow parts run as tasks on it
+    pub fn handle(&self, action: Action) {
+        let current_state = *self.state.lock().unwrap();
+        info!(target: "kiosk", "Action {:?}, current state: {:?}", action, current_state);
+
+        match action {
+            Action::ToggleMeeting => self.toggle_meeting(current_state, 0),
+            Action::JoinMeeting(meeting) => self.toggle_meeting(current_state, meeting),
+            Action::Hangup => match current_state {
+                AutomationState::ZoomComplete => {
+                    self.stop_meeting();
+                }
+                _ => info!(target: "kiosk", "Not in a meeting, ignoring hang up"),
+            },
+            Action::Mute => match self.current_meeting.lock().unwrap().clone() {
+                Some(session) if current_state == AutomationState::ZoomComplete => {
+                    tokio::spawn(toggle_mute(session));
+                }
+                _ => info!(target: "kiosk", "Not in a meeting, ignoring mute"),
+            },
+            Action::VolumeUp => {
+                tokio::spawn(change_volume(format!("+{}", VOLUME_STEP)));
+            }
+            Action::VolumeDown => {
+                tokio::spawn(change_volume(format!("-{}", VOLUME_STEP)));
+            }
+            Action::NextSlide | Action::PrevSlide if current_state != AutomationState::KioskMode => {
+                info!(target: "kiosk", "Not in kiosk mode, ignoring slide change");
+            }
+            Action::NextSlide => self.app_handle.state::<KioskControl>().request(KioskRequest::Next),
+            Action::PrevSlide => self.app_handle.state::<KioskControl>().request(KioskRequest::Previous),
+        }
+    }
+
+    // Joins `meeting` from kiosk mode, otherwise acts on the meeting we're in, whichever it is
+    fn toggle_meeting(&self, current_state: AutomationState, meeting: usize) {
+        match current_state {
+            AutomationState::KioskMode => {
+                self.start_meeting(meeting);
+            }
+            AutomationState::ZoomRunning => {
+                info!(target: "kiosk", "Zoom automation already running, ignoring signal");
+            }
+            AutomationState::ZoomComplete if self.meeting_clock.lock().unwrap().extend(&self.config.meeting) => {
+                info!(target: "kiosk", "Button pressed during the end of meeting warning, extending the meeting by {} minutes", self.config.meeting.extension_minutes);
+            }
+            AutomationState::ZoomComplete => {
+                self.stop_meeting();
+            }
+            AutomationState::Stopping => {
+                info!(target: "kiosk", "Currently stopping processes, ignoring signal");
+            }
+            AutomationState::Updating => {
+                info!(target: "kiosk", "Restarting for an update, ignoring signal");
+            }
+        }
+    }
+
+    // Moves from one state to another under a single lock, so of two inputs acting at the same
+    // time only one gets to. False if the state wasn't `from` anymore.
+    fn transition(&self, from: AutomationState, to: AutomationState) -> bool {
+        let mut state_guard = self.state.lock().unwrap();
+        if *state_guard != from {
+            info!(target: "kiosk", "State changed to {:?} in the meantime, ignoring signal", *state_guard);
+            return false;
+        }
+        *state_guard = to;
+        true
+    }
+
+    // Joins `config.meetings[meeting]`. False if not in kiosk mode (anymore), in which case
+    // nothing is done.
+    pub fn start_meeting(&self, meeting: usize) -> bool {
+        if !self.transition(AutomationState::KioskMode, AutomationState::ZoomRunning) {
+            return false;
+        }
+        info!(target: "kiosk", "Switching from Kiosk to meeting mode...");
+
+        // Hide the Tauri window
+        if let Some(window) = self.app_handle.get_webview_window("main") {
+            let _ = window.hide();
+        }
+
+        // Start meeting automation in background task
+        metrics::MEETING_JOINS_ATTEMPTED.inc();
+        let controller = self.clone();
+        tokio::spawn(async move {
+            // Start the meeting automation. It has its own deadlines (see MeetingSettings), and
+            // once we're in, the meeting watcher takes care of the maximum meeting length.
+            let config = Arc::clone(&controller.config);
+            let meeting_app_handle = Arc::clone(&controller.app_handle);
+            let zoom_result = tokio::spawn(async move {
+                meeting::start_meeting(&config, &config.meetings[meeting], &meeting_app_handle).await
+            });
+
+            let Controller { token, config, app_handle, state, meeting_clock, current_meeting, .. } = controller;
+            match zoom_result.await {
+                Ok(Ok(session)) => {
+                    info!(target: "zoom", "Meeting automation completed successfully");
+                    metrics::MEETING_JOINS_SUCCEEDED.inc();
+                    {
+                        let mut state_guard = state.lock().unwrap();
+                        *state_guard = AutomationState::ZoomComplete;
+                    }
+                    let session = Arc::new(session);
+                    *current_meeting.lock().unwrap() = Some(Arc::clone(&session));
+                    tokio::spawn(async move {
+                        meeting::watch_meeting(Arc::clone(&session), config.meeting.clone(), meeting_clock, token, app_handle, state).await;
+                        // Unless a new meeting has been started since
+                        let mut current = current_meeting.lock().unwrap();
+                        if current.as_ref().is_some_and(|current| Arc::ptr_eq(current, &session)) {
+                            *current = None;
+                        }
+                    });
+                }
+                Ok(Err(e)) => {
+                    warn!(target: "zoom", "Meeting automation {} (step: {:?}), returning to kiosk mode", e, e.step);
+                    metrics::MEETING_JOINS_FAILED.with_label_values(&[format!("{:?}", e.step).as_str()]).inc();
+                    kill_chrome_processes().await;
+                    return_to_kiosk_mode(token, app_handle, state);
+                }
+                Err(e) => {
+                    error!(target: "zoom", "Meeting task panicked: {}, returning to kiosk mode", e);
+                    metrics::MEETING_JOINS_FAILED.with_label_values(&["panic"]).inc();
+                    kill_chrome_processes().await;
+                    return_to_kiosk_mode(token, app_handle, state);
+                }
+            }
+        });
+        true
+    }
+
+    // False if not in a meeting (anymore), in which case nothing is done
+    pub fn stop_meeting(&self) -> bool {
+        if !self.transition(AutomationState::ZoomComplete, AutomationState::Stopping) {
+            return false;
+        }
+        info!(target: "kiosk", "Stopping Zoom and returning to Kiosk mode...");
+        self.current_meeting.lock().unwrap().take();
+
+        // Kill Chrome processes and restart kiosk in background task
+        let state_clone = Arc::clone(&self.state);
+        let app_handle_clone = Arc::clone(&self.app_handle);
+        let token_clone = self.token.clone();
+        tokio::spawn(async move {
+            kill_chrome_processes().await;
+            info!(target: "process", "Chrome processes stopped, returning to kiosk mode");
+            return_to_kiosk_mode(token_clone, app_handle_clone, state_clone);
+        });
+        true
+    }
+}
+
+// The policy is updated too, otherwise the meeting watcher would put the microphone back
+async fn toggle_mute(session: Arc<MeetingSession>) {
+    let muted = match session.provider.info(&session.browser).await {
+        Ok(info) => info.audio_muted,
+        Err(e) => {
+            warn!(target: "zoom", "Could not check the microphone: {}", e);
+            return;
+        }
+    };
+    let Some(muted) = muted else {
+        warn!(target: "zoom", "Microphone state unknown, not toggling mute");
+        return;
+    };
+
+    match session.provider.set_muted(&session.browser, !muted).await {
+        Ok(()) => {
+            info!(target: "zoom", "Microphone {}", if muted { "unmuted" } else { "muted" });
+            let mut policy = session.policy.lock().unwrap();
+            if policy.mic_muted.is_some() {
+                policy.mic_muted = Some(!muted);
+            }
+        }
+        Err(e) => warn!(target: "zoom", "Could not toggle mute: {}", e),
+    }
+}
+
+// Default PulseAudio / PipeWire output, which is the Jabra when it's plugged in
+async fn change_volume(step: String) {
+    match Command::new("pactl").args(["set-sink-volume", "@DEFAULT_SINK@", &step]).status().await {
+        Ok(status) if status.success() => info!(target: "process", "Volume {}", step),
+        Ok(status) => warn!(target: "process", "pactl failed to change the volume: {}", status),
+        Err(e) => warn!(target: "process", "Could not run pactl to change the volume: {}", e),
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::config::DiagnosticsConfig;
+use crate::meeting::Browser;
+use crate::util::unix_time;
+
+// Returns the HTML of every same-origin iframe, the Zoom web client lives in one
+const FRAME_SOURCES_SCRIPT: &str = r#"
+    return Array.from(document.querySelectorAll('iframe')).map((frame) => {
+        try { return frame.contentDocument.documentElement.outerHTML; } catch (e) { return null; }
+    });
+"#;
+
+// Saves a screenshot, the current URL and the page source (including iframes) into
+// `<dir>/<unix timestamp>-<label>/`, then deletes the oldest snapshots past the limit.
+pub async fn save_snapshot(browser: &Browser, config: &DiagnosticsConfig, label: &str, error: &str) {
+    if config.max_snapshots == 0 {
+        return;
+    }
+
+    let dir = Path::new(&config.dir).join(format!("{}-{}", unix_time(), label));
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        warn!(target: "zoom", "Could not create diagnostics directory {}: {}", dir.display(), e);
+        return;
+    }
+
+    write_file(&dir, "error.txt", error.as_bytes());
+
+    // The browser may be what's broken, don't let it hold up the return to kiosk mode
+    if tokio::time::timeout(Duration::from_secs(30), capture(browser, &dir)).await.is_err() {
+        warn!(target: "zoom", "Timed out while saving diagnostics to {}", dir.display());
+    } else {
+        info!(target: "zoom", "Saved diagnostics to {}", dir.display());
+    }
+
+    prune_snapshots(Path::new(&config.dir), config.max_snapshots);
+}
+
+async fn capture(browser: &Browser, dir: &Path) {
+    match browser.current_url().await {
+        Ok(url) => write_file(dir, "url.txt", url.as_bytes()),
+        Err(e) => warn!(target: "zoom", "Could not get current URL for diagnostics: {}", e),
+    }
+
+    match browser.screenshot_png().await {
+        Ok(png) => write_file(dir, "screenshot.png", &png),
+        Err(e) => warn!(target: "zoom", "Could not take screenshot for diagnostics: {}", e),
+    }
+
+    match browser.source().await {
+        Ok(source) => write_file(dir, "page.html", source.as_bytes()),
+        Err(e) => warn!(target: "zoom", "Could not get page source for diagnostics: {}", e),
+    }
+
+    match browser.execute(FRAME_SOURCES_SCRIPT, Vec::new()).await {
+        Ok(result) => {
+            if let Some(frames) = result.as_array() {
+                for (i, frame) in frames.iter().enumerate() {
+                    if let Some(source) = frame.as_str() {
+                        write_file(dir, &format!("iframe-{}.html", i), source.as_bytes());
+                    }
+                }
+            }
+        }
+        Err(e) => warn!(target: "zoom", "Could not get iframe sources for diagnostics: {}", e),
+    }
+}
+
+fn write_file(dir: &Path, name: &str, contents: &[u8]) {
+    if let Err(e) = std::fs::write(dir.join(name), contents) {
+        warn!(target: "zoom", "Could not write diagnostics file {}: {}", name, e);
+    }
+}
+
+// Snapshot directory names start with a timestamp, so sorting by name sorts by age
+fn prune_snapshots(dir: &Path, max_snapshots: usize) {
+    let mut snapshots: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.path())
+            .collect(),
+        Err(e) => {
+            warn!(target: "zoom", "Could not list diagnostics directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    snapshots.sort();
+
+    let excess = snapshots.len().saturating_sub(max_snapshots);
+    for snapshot in &snapshots[..excess] {
+        if let Err(e) = std::fs::remove_dir_all(snapshot) {
+            warn!(target: "zoom", "Could not delete old diagnostics {}: {}", snapshot.display(), e);
+        }
+    }
+}
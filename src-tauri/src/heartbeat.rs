@@ -0,0 +1,393 @@
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{debug, error, info, warn, Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use crate::config::HeartbeatConfig;
+use crate::controls::Controller;
+use crate::status::StatusReport;
+use crate::util::unix_time;
+
+// Past this many, warnings and errors are still counted but no longer kept for the next heartbeat
+const MAX_RECORDS: usize = 200;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+struct LogRecord {
+    timestamp: u64,
+    level: String,
+    target: String,
+    message: String,
+}
+
+#[derive(Default)]
+struct RecentLogs {
+    records: Vec<LogRecord>,
+    warnings: u64,
+    errors: u64,
+}
+
+// What has been logged since the last heartbeat
+static RECENT_LOGS: Mutex<RecentLogs> = Mutex::new(RecentLogs { records: Vec::new(), warnings: 0, errors: 0 });
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+// Keeps the warnings and errors that get past the log filter, for the next heartbeat
+pub struct ErrorCollector;
+
+impl<S: Subscriber> Layer<S> for ErrorCollector {
+    fn on_event(&self, event: &Event<'_>, _context: Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level > Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for field in visitor.fields {
+            message.push(' ');
+            message.push_str(&field);
+        }
+
+        let mut logs = RECENT_LOGS.lock().unwrap();
+        if level == Level::ERROR {
+            logs.errors += 1;
+        } else {
+            logs.warnings += 1;
+        }
+        if logs.records.len() < MAX_RECORDS {
+            logs.records.push(LogRecord {
+                timestamp: unix_time(),
+                level: level.to_string(),
+                target: event.metadata().target().to_string(),
+                message,
+            });
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Heartbeat {
+    sent_at: u64, // When the heartbeat was made, it may be delivered much later
+    status: StatusReport,
+    warnings: u64, // Since the last heartbeat
+    errors: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logs: Option<Vec<LogRecord>>,
+}
+
+async fn take_heartbeat(controller: &Controller, config: &HeartbeatConfig) -> Heartbeat {
+    let logs = std::mem::take(&mut *RECENT_LOGS.lock().unwrap());
+    Heartbeat {
+        sent_at: unix_time(),
+        status: StatusReport::collect(controller).await,
+        warnings: logs.warnings,
+        errors: logs.errors,
+        logs: config.ship_logs.then_some(logs.records),
+    }
+}
+
+// Lines that aren't JSON, e.g. cut short by a power cut, are dropped
+fn read_buffer(path: &str) -> Vec<String> {
+    match std::fs::read(path) {
+        Ok(contents) => {
+            let contents = String::from_utf8_lossy(&contents);
+            let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+            let pending: Vec<String> = lines
+                .iter()
+                .filter(|line| serde_json::from_str::<serde::de::IgnoredAny>(line).is_ok())
+                .map(|line| line.to_string())
+                .collect();
+            if pending.len() < lines.len() {
+                warn!(target: "process", "Dropping {} unreadable buffered heartbeats from {}", lines.len() - pending.len(), path);
+            }
+            pending
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            warn!(target: "process", "Could not read buffered heartbeats from {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+fn write_buffer(path: &str, pending: &[String]) {
+    if pending.is_empty() {
+        if let Err(e) = std::fs::remove_file(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(target: "process", "Could not remove {}: {}", path, e);
+            }
+        }
+        return;
+    }
+    let result = OpenOptions::new().write(true).create(true).truncate(true).open(path).and_then(|mut file| {
+        for line in pending {
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        warn!(target: "process", "Could not buffer heartbeats in {}: {}", path, e);
+    }
+}
+
+enum Delivery {
+    Sent,
+    Rejected(StatusCode), // The server won't ever take it, no point in keeping it
+    Failed(String),
+}
+
+async fn send(client: &reqwest::Client, url: &str, token: &str, body: &str) -> Delivery {
+    let response = client
+        .post(url)
+        .query(&[("tv_login_token", token)])
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => Delivery::Sent,
+        Ok(response)
+            if response.status().is_client_error()
+                && !matches!(response.status(), StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS) =>
+        {
+            Delivery::Rejected(response.status())
+        }
+        Ok(response) => Delivery::Failed(format!("server answered {}", response.status())),
+        Err(e) => Delivery::Failed(e.to_string()),
+    }
+}
+
+struct Flush {
+    delivered: usize,
+    failure: Option<String>, // Why the rest was buffered
+}
+
+// Sends the buffered heartbeats oldest first, then `heartbeat`. What couldn't be sent is
+// buffered again, up to the newest `heartbeat.max_buffered`.
+async fn flush(client: &reqwest::Client, url: &str, token: &str, config: &HeartbeatConfig, heartbeat: Option<String>) -> Flush {
+    let had_buffer = Path::new(&config.buffer_file).exists();
+    let mut pending = read_buffer(&config.buffer_file);
+    pending.extend(heartbeat);
+
+    let mut delivered = 0;
+    let mut failure = None;
+    while let Some(line) = pending.first() {
+        match send(client, url, token, line).await {
+            Delivery::Sent => delivered += 1,
+            Delivery::Rejected(status) => warn!(target: "process", "Heartbeat rejected by the server ({}), dropping it", status),
+            Delivery::Failed(e) => {
+                failure = Some(e);
+                break;
+            }
+        }
+        pending.remove(0);
+    }
+
+    if pending.len() > config.max_buffered {
+        let dropped = pending.len() - config.max_buffered;
+        pending.drain(..dropped);
+    }
+    // Only touch the file when there was or is something in it
+    if had_buffer || !pending.is_empty() {
+        write_buffer(&config.buffer_file, &pending);
+    }
+    Flush { delivered, failure }
+}
+
+// Posts a heartbeat to `heartbeat.url` every interval, with the TV token. Heartbeats that can't
+// be delivered are kept in `heartbeat.buffer_file`, and sent oldest first once the server is back.
+pub async fn start_heartbeat(controller: Controller, token: String) {
+    let config = controller.config.heartbeat.clone();
+    let Some(url) = config.url.clone() else { return };
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!(target: "process", "Heartbeats disabled, could not set up an HTTP client: {}", e);
+            return;
+        }
+    };
+    info!(target: "process", "Sending heartbeats to {} every {} seconds", url, config.interval_seconds);
+
+    let mut offline_since: Option<u64> = None;
+    let mut interval = tokio::time::interval(config.interval());
+    loop {
+        interval.tick().await;
+
+        let heartbeat = serde_json::to_string(&take_heartbeat(&controller, &config).await)
+            .map_err(|e| warn!(target: "process", "Could not encode heartbeat: {}", e))
+            .ok();
+        let Flush { delivered, failure } = flush(&client, &url, &token, &config, heartbeat).await;
+
+        match (failure, offline_since) {
+            (Some(e), None) => {
+                warn!(target: "process", "Could not send heartbeat: {}, buffering until the server is back", e);
+                offline_since = Some(unix_time());
+            }
+            (Some(e), Some(_)) => debug!(target: "process", "Still could not send heartbeat: {}", e),
+            (None, Some(since)) => {
+                info!(target: "process", "Heartbeats delivered again after {} seconds offline, {} sent", unix_time().saturating_sub(since), delivered);
+                offline_since = None;
+            }
+            (None, None) => debug!(target: "process", "Heartbeat sent"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use axum::extract::{RawQuery, State};
+    use axum::routing::post;
+    use axum::Router;
+
+    fn buffer_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rctv-heartbeat-test-{}-{}.jsonl", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn beat(n: u32) -> String {
+        format!(r#"{{"n":{}}}"#, n)
+    }
+
+    #[test]
+    fn buffer_round_trip() {
+        let path = buffer_path("round-trip");
+        assert!(read_buffer(&path).is_empty());
+        write_buffer(&path, &[beat(1), beat(2)]);
+        assert_eq!(read_buffer(&path), [beat(1), beat(2)]);
+        // Emptying it removes the file
+        write_buffer(&path, &[]);
+        assert!(!Path::new(&path).exists());
+        write_buffer(&path, &[]);
+    }
+
+    #[test]
+    fn corrupt_buffer_keeps_what_it_can() {
+        let path = buffer_path("corrupt");
+        let mut contents = format!("{}\n\n{}\n", beat(1), beat(2)).into_bytes();
+        contents.extend_from_slice(b"{\"n\":3, \xff\xfe\n{\"n\":");
+        std::fs::write(&path, contents).unwrap();
+        assert_eq!(read_buffer(&path), [beat(1), beat(2)]);
+        std::fs::write(&path, [0xffu8, 0x00, 0x12]).unwrap();
+        assert!(read_buffer(&path).is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // Query string and body of each request
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    // Records what it gets. Heartbeats with "reject" in them get a 400, with "fail" a 503.
+    async fn start_server() -> (String, Received) {
+        async fn receive(State(received): State<Received>, RawQuery(query): RawQuery, body: String) -> reqwest::StatusCode {
+            let status = if body.contains("reject") {
+                reqwest::StatusCode::BAD_REQUEST
+            } else if body.contains("fail") {
+                reqwest::StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                reqwest::StatusCode::NO_CONTENT
+            };
+            received.lock().unwrap().push((query.unwrap_or_default(), body));
+            status
+        }
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let router = Router::new().route("/heartbeat", post(receive)).with_state(Arc::clone(&received));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/heartbeat", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, received)
+    }
+
+    // Nothing listens there
+    async fn unreachable_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}/heartbeat", listener.local_addr().unwrap())
+    }
+
+    fn bodies(received: &Mutex<Vec<(String, String)>>) -> Vec<String> {
+        received.lock().unwrap().iter().map(|(_, body)| body.clone()).collect()
+    }
+
+    #[test]
+    fn buffers_while_offline_then_sends_in_order() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let config = HeartbeatConfig { buffer_file: buffer_path("offline"), max_buffered: 3, ..HeartbeatConfig::default() };
+            let client = reqwest::Client::new();
+
+            let offline = unreachable_url().await;
+            for n in 1..=5 {
+                let flush = flush(&client, &offline, "token", &config, Some(beat(n))).await;
+                assert_eq!(flush.delivered, 0);
+                assert!(flush.failure.is_some());
+            }
+            // Only the newest are kept
+            assert_eq!(read_buffer(&config.buffer_file), [beat(3), beat(4), beat(5)]);
+
+            let (url, received) = start_server().await;
+            let flush = flush(&client, &url, "token", &config, Some(beat(6))).await;
+            assert_eq!(flush.delivered, 4);
+            assert!(flush.failure.is_none());
+            assert_eq!(bodies(&received), [beat(3), beat(4), beat(5), beat(6)]);
+            assert!(received.lock().unwrap().iter().all(|(query, _)| query == "tv_login_token=token"));
+            assert!(!Path::new(&config.buffer_file).exists());
+        });
+    }
+
+    #[test]
+    fn drops_rejected_heartbeats_and_stops_at_failures() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let config = HeartbeatConfig { buffer_file: buffer_path("rejected"), ..HeartbeatConfig::default() };
+            let client = reqwest::Client::new();
+            let (url, received) = start_server().await;
+
+            let reject = r#"{"reject":true}"#.to_string();
+            let fail = r#"{"fail":true}"#.to_string();
+            write_buffer(&config.buffer_file, &[beat(1), reject.clone(), beat(2), fail.clone(), beat(3)]);
+            let flush = flush(&client, &url, "token", &config, Some(beat(4))).await;
+            assert_eq!(flush.delivered, 2);
+            assert!(flush.failure.is_some_and(|e| e.contains("503")));
+            // The server still has a problem with the 503 one, it stays first in line
+            assert_eq!(bodies(&received), [beat(1), reject, beat(2), fail.clone()]);
+            assert_eq!(read_buffer(&config.buffer_file), [fail, beat(3), beat(4)]);
+            let _ = std::fs::remove_file(&config.buffer_file);
+        });
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::gestures::Edge;
+use super::{device_matches, run_bindings, sort_devices, Binding, EdgeSender, InputDevice};
+use crate::config::EvdevConfig;
+use crate::controls::Controller;
+use crate::metrics;
+
+// How often to look for newly plugged in keyboards and remotes
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+const EV_KEY: u16 = 0x01;
+
+// EVIOCGRAB, _IOW('E', 0x90, int)
+const EVIOCGRAB: libc::c_ulong = 0x40044590;
+
+// Key names accepted in the config (with or without the KEY_ prefix), from
+// linux/input-event-codes.h. Presenter remotes mostly send PageUp/PageDown, B (blank
+// screen), F5 / Esc (start / stop presentation) and the arrows.
+const KEY_CODES: &[(&str, u16)] = &[
+    ("ESC", 1),
+    ("TAB", 15),
+    ("Q", 16),
+    ("ENTER", 28),
+    ("H", 35),
+    ("B", 48),
+    ("N", 49),
+    ("M", 50),
+    ("DOT", 52),
+    ("SPACE", 57),
+    ("F5", 63),
+    ("HOME", 102),
+    ("UP", 103),
+    ("PAGEUP", 104),
+    ("LEFT", 105),
+    ("RIGHT", 106),
+    ("END", 107),
+    ("DOWN", 108),
+    ("PAGEDOWN", 109),
+    ("MUTE", 113),
+    ("VOLUMEDOWN", 114),
+    ("VOLUMEUP", 115),
+    ("PLAYPAUSE", 164),
+    ("NEXTSONG", 163),
+    ("PREVIOUSSONG", 165),
+];
+
+// "KEY_PAGEDOWN", "pagedown" or a key code as a number
+pub fn key_code(name: &str) -> Option<u16> {
+    if let Ok(code) = name.parse() {
+        return Some(code);
+    }
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("KEY_").unwrap_or(&name);
+    KEY_CODES.iter().find(|(key, _)| *key == name).map(|(_, code)| *code)
+}
+
+// Every /dev/input/event* device, by number
+pub fn list_devices() -> Vec<InputDevice> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/input") else { return Vec::new() };
+    let mut devices: Vec<InputDevice> = entries
+        .filter_map(|entry| {
+            let node = entry.ok()?.file_name().to_string_lossy().into_owned();
+            node.starts_with("event").then(|| read_device(&node))?
+        })
+        .collect();
+    sort_devices(&mut devices);
+    devices
+}
+
+// /sys/class/input/eventN/device is the input device, with name, phys, uniq and id/*
+fn read_device(node: &str) -> Option<InputDevice> {
+    let sysfs = PathBuf::from("/sys/class/input").join(node).join("device");
+    let read = |file: &str| std::fs::read_to_string(sysfs.join(file)).map(|value| value.trim().to_string()).unwrap_or_default();
+    Some(InputDevice {
+        path: PathBuf::from("/dev/input").join(node),
+        name: std::fs::read_to_string(sysfs.join("name")).ok()?.trim().to_string(),
+        vendor_id: u16::from_str_radix(&read("id/vendor"), 16).unwrap_or(0),
+        product_id: u16::from_str_radix(&read("id/product"), 16).unwrap_or(0),
+        serial: read("uniq"),
+        phys: read("phys"),
+    })
+}
+
+fn bindings(config: &EvdevConfig) -> Vec<Binding> {
+    config
+        .keys
+        .iter()
+        .map(|key| Binding {
+            label: key.key.clone(),
+            action: key.action,
+            long_press: key.long_press,
+            double_press: key.double_press,
+            has_release: true,
+        })
+        .collect()
+}
+
+// Reads key events until the device goes away. Autorepeat counts as the key still being down.
+fn read_events(mut file: File, codes: &[u16], sender: &EdgeSender) -> std::io::Result<()> {
+    let mut buffer = [0u8; std::mem::size_of::<libc::input_event>()];
+    loop {
+        file.read_exact(&mut buffer)?;
+        // SAFETY: input_event is plain old data, and the kernel hands out whole events
+        let event: libc::input_event = unsafe { std::ptr::read_unaligned(buffer.as_ptr().cast()) };
+        if event.type_ != EV_KEY {
+            continue;
+        }
+        let Some(index) = codes.iter().position(|code| *code == event.code) else { continue };
+        metrics::INPUT_EVENTS.with_label_values(&["evdev"]).inc();
+        let edge = if event.value == 0 { Edge::Release } else { Edge::Press };
+        if sender.send(Ok((index, edge))).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn open_device(device: &InputDevice, grab: bool) -> std::io::Result<File> {
+    let file = File::open(&device.path)?;
+    // Keeps the keys from also reaching the kiosk page (PageDown would scroll it...)
+    if grab && unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB as _, 1 as libc::c_int) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+// Listens to every device matching `evdev.devices`, picking up new ones as they're plugged in.
+// All of them share the same key bindings.
+pub async fn start_evdev_controller(controller: Controller) {
+    let config = &controller.config.evdev;
+    // Key names were checked when loading the config
+    let codes: Arc<Vec<u16>> = Arc::new(config.keys.iter().map(|key| key_code(&key.key).unwrap_or_default()).collect());
+    let (sender, receiver): (EdgeSender, _) = tokio::sync::mpsc::unbounded_channel();
+
+    info!(target: "hid", "Keyboard input started, monitoring for keys:");
+    for key in &config.keys {
+        debug!(target: "hid", "  {} -> {:?} (long press: {:?}, double press: {:?})", key.key, key.action, key.long_press, key.double_press);
+    }
+
+    // Devices with a reader thread, which removes its device when it's unplugged
+    let open: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+    let rescan_config = config.clone();
+    tokio::spawn(async move {
+        // Only complain once about a device we can't open (permissions usually)
+        let mut failed = HashSet::new();
+        loop {
+            for device in list_devices() {
+                if !rescan_config.devices.iter().any(|rule| device_matches(rule, &device)) || !open.lock().unwrap().insert(device.path.clone()) {
+                    continue;
+                }
+                let file = match open_device(&device, rescan_config.grab) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        if failed.insert(device.path.clone()) {
+                            warn!(target: "hid", "Could not open input device {}: {}", device, e);
+                        }
+                        open.lock().unwrap().remove(&device.path);
+                        continue;
+                    }
+                };
+                failed.remove(&device.path);
+                info!(target: "hid", "Input device connected: {}", device);
+                let (codes, sender, open) = (Arc::clone(&codes), sender.clone(), Arc::clone(&open));
+                std::thread::spawn(move || {
+                    let reason = match read_events(file, &codes, &sender) {
+                        Ok(()) => "closed".to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    info!(target: "hid", "Input device {} disconnected: {}", device.path.display(), reason);
+                    open.lock().unwrap().remove(&device.path);
+                });
+            }
+            tokio::time::sleep(RESCAN_INTERVAL).await;
+        }
+    });
+
+    // Devices come and go, but the rescan task keeps a sender, so this only ends with the app
+    let _ = run_bindings(&controller, &bindings(config), receiver).await;
+}
@@ -0,0 +1,307 @@
+use std::time::{Duration, Instant};
+
+use crate::config::GestureConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Short,
+    Long,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Press,   // The button went down, or the device repeated its report while held
+    Release,
+}
+
+// Timing for one button. Long and double press are only waited for when the button does
+// something on them, so a plain button reacts as soon as it's released.
+#[derive(Debug, Clone, Copy)]
+pub struct GestureTiming {
+    pub debounce: Duration,
+    pub long_press: Option<Duration>,
+    pub double_press: Option<Duration>,
+    // Without release events, a press is a burst of reports and ends after `debounce` of silence
+    pub has_release: bool,
+}
+
+impl GestureTiming {
+    pub fn new(config: &GestureConfig, has_release: bool, long_press: bool, double_press: bool) -> GestureTiming {
+        GestureTiming {
+            debounce: config.debounce(),
+            long_press: long_press.then(|| config.long_press()),
+            double_press: double_press.then(|| config.double_press()),
+            has_release,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle { ignore_until: Option<Instant> },
+    Pressed { since: Instant, last_report: Instant, handled: bool }, // `handled` once a long or double press fired
+    AwaitingSecond { until: Instant, ignore_until: Option<Instant> },
+}
+
+// Turns the press/release events of one button into gestures. Time is passed in rather than
+// read, so event sequences can be replayed with made up timestamps.
+#[derive(Debug, Clone)]
+pub struct GestureDetector {
+    timing: GestureTiming,
+    state: State,
+}
+
+impl GestureDetector {
+    pub fn new(timing: GestureTiming) -> GestureDetector {
+        GestureDetector { timing, state: State::Idle { ignore_until: None } }
+    }
+
+    // Call `poll` first, so timers that ran out before this event are handled in order
+    pub fn input(&mut self, edge: Edge, now: Instant) -> Option<Gesture> {
+        match (edge, self.state) {
+            (Edge::Press, State::Idle { ignore_until }) => {
+                if ignore_until.is_some_and(|until| now < until) {
+                    return None; // Contact bounce right after a release
+                }
+                self.state = State::Pressed { since: now, last_report: now, handled: false };
+                None
+            }
+            (Edge::Press, State::Pressed { since, handled, .. }) => {
+                self.state = State::Pressed { since, last_report: now, handled };
+                None
+            }
+            (Edge::Press, State::AwaitingSecond { ignore_until, .. }) => {
+                if ignore_until.is_some_and(|until| now < until) {
+                    return None;
+                }
+                self.state = State::Pressed { since: now, last_report: now, handled: true };
+                Some(Gesture::Double)
+            }
+            (Edge::Release, State::Pressed { handled, .. }) => self.end_press(now, handled, Some(now + self.timing.debounce)),
+            (Edge::Release, _) => None,
+        }
+    }
+
+    // Call at `next_deadline` (or any time after): fires gestures that depend on time passing
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        match self.state {
+            State::Idle { .. } => None,
+            State::Pressed { since, last_report, handled } => {
+                let burst_end = (!self.timing.has_release).then(|| last_report + self.timing.debounce);
+                let long_at = self.timing.long_press.filter(|_| !handled).map(|long_press| since + long_press);
+                match (burst_end, long_at) {
+                    // The burst of reports ended before it got long
+                    (Some(end), long_at) if now >= end && long_at.is_none_or(|long_at| end < long_at) => {
+                        self.end_press(now, handled, None)
+                    }
+                    (_, Some(long_at)) if now >= long_at => {
+                        self.state = State::Pressed { since, last_report, handled: true };
+                        Some(Gesture::Long)
+                    }
+                    _ => None,
+                }
+            }
+            State::AwaitingSecond { until, .. } if now >= until => {
+                self.state = State::Idle { ignore_until: None };
+                Some(Gesture::Short)
+            }
+            State::AwaitingSecond { .. } => None,
+        }
+    }
+
+    // When `poll` has something to do next
+    pub fn next_deadline(&self) -> Option<Instant> {
+        match self.state {
+            State::Idle { .. } => None,
+            State::Pressed { since, last_report, handled } => {
+                let burst_end = (!self.timing.has_release).then(|| last_report + self.timing.debounce);
+                let long_at = self.timing.long_press.filter(|_| !handled).map(|long_press| since + long_press);
+                [burst_end, long_at].into_iter().flatten().min()
+            }
+            State::AwaitingSecond { until, .. } => Some(until),
+        }
+    }
+
+    fn end_press(&mut self, now: Instant, handled: bool, ignore_until: Option<Instant>) -> Option<Gesture> {
+        self.state = State::Idle { ignore_until };
+        if handled {
+            return None;
+        }
+        match self.timing.double_press {
+            Some(double_press) => {
+                self.state = State::AwaitingSecond { until: now + double_press, ignore_until };
+                None
+            }
+            None => Some(Gesture::Short),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE: u64 = 50;
+    const LONG_PRESS: u64 = 1500;
+    const DOUBLE_PRESS: u64 = 400;
+
+    fn timing(has_release: bool, long_press: bool, double_press: bool) -> GestureTiming {
+        GestureTiming {
+            debounce: Duration::from_millis(DEBOUNCE),
+            long_press: long_press.then_some(Duration::from_millis(LONG_PRESS)),
+            double_press: double_press.then_some(Duration::from_millis(DOUBLE_PRESS)),
+            has_release,
+        }
+    }
+
+    // Calls `poll` at every deadline up to `limit` (all of them if None), the way run_bindings does
+    fn poll_until(detector: &mut GestureDetector, start: Instant, limit: Option<Instant>, gestures: &mut Vec<(u64, Gesture)>) {
+        for _ in 0..100 {
+            let Some(deadline) = detector.next_deadline() else { return };
+            if limit.is_some_and(|limit| deadline > limit) {
+                return;
+            }
+            if let Some(gesture) = detector.poll(deadline) {
+                gestures.push(((deadline - start).as_millis() as u64, gesture));
+            }
+        }
+        panic!("poll never got past {:?}", detector.next_deadline());
+    }
+
+    // Feeds edges at the given milliseconds, then lets time run out. Returns the gestures and
+    // when they fired.
+    fn replay(timing: GestureTiming, events: &[(u64, Edge)]) -> Vec<(u64, Gesture)> {
+        let start = Instant::now();
+        let mut detector = GestureDetector::new(timing);
+        let mut gestures = Vec::new();
+        for &(ms, edge) in events {
+            let now = start + Duration::from_millis(ms);
+            poll_until(&mut detector, start, Some(now), &mut gestures);
+            if let Some(gesture) = detector.input(edge, now) {
+                gestures.push((ms, gesture));
+            }
+        }
+        poll_until(&mut detector, start, None, &mut gestures);
+        gestures
+    }
+
+    // A button held down for a device without release reports: the same report every `every` ms
+    fn burst(from: u64, to: u64, every: u64) -> Vec<(u64, Edge)> {
+        (from..=to).step_by(every as usize).map(|ms| (ms, Edge::Press)).collect()
+    }
+
+    #[test]
+    fn short_press_with_release() {
+        assert_eq!(replay(timing(true, false, false), &[(0, Edge::Press), (100, Edge::Release)]), [(100, Gesture::Short)]);
+        // Holding it doesn't matter without a long press action
+        assert_eq!(replay(timing(true, false, false), &[(0, Edge::Press), (5000, Edge::Release)]), [(5000, Gesture::Short)]);
+    }
+
+    #[test]
+    fn short_press_waits_for_a_possible_second_one() {
+        let gestures = replay(timing(true, true, true), &[(0, Edge::Press), (100, Edge::Release)]);
+        assert_eq!(gestures, [(100 + DOUBLE_PRESS, Gesture::Short)]);
+    }
+
+    #[test]
+    fn long_press_fires_while_held() {
+        let events = [(0, Edge::Press), (2000, Edge::Release)];
+        assert_eq!(replay(timing(true, true, true), &events), [(LONG_PRESS, Gesture::Long)]);
+        // Released just before
+        let events = [(0, Edge::Press), (LONG_PRESS - 1, Edge::Release)];
+        assert_eq!(replay(timing(true, true, false), &events), [(LONG_PRESS - 1, Gesture::Short)]);
+    }
+
+    #[test]
+    fn double_press_with_release() {
+        let events = [(0, Edge::Press), (100, Edge::Release), (300, Edge::Press), (400, Edge::Release)];
+        assert_eq!(replay(timing(true, true, true), &events), [(300, Gesture::Double)]);
+        // Too late for a double press: two short ones
+        let events = [(0, Edge::Press), (100, Edge::Release), (600, Edge::Press), (700, Edge::Release)];
+        assert_eq!(replay(timing(true, false, true), &events), [(500, Gesture::Short), (1100, Gesture::Short)]);
+        // Holding the second press doesn't also make it long
+        let events = [(0, Edge::Press), (100, Edge::Release), (300, Edge::Press), (3000, Edge::Release)];
+        assert_eq!(replay(timing(true, true, true), &events), [(300, Gesture::Double)]);
+    }
+
+    #[test]
+    fn bounce_after_release_is_ignored() {
+        let bounce = [(0, Edge::Press), (100, Edge::Release), (120, Edge::Press), (130, Edge::Release)];
+        assert_eq!(replay(timing(true, false, false), &bounce), [(100, Gesture::Short)]);
+        // Not taken for a second press either
+        assert_eq!(replay(timing(true, false, true), &bounce), [(100 + DOUBLE_PRESS, Gesture::Short)]);
+        // Past the debounce, it's a real press
+        let events = [(0, Edge::Press), (100, Edge::Release), (100 + DEBOUNCE, Edge::Press), (200, Edge::Release)];
+        assert_eq!(replay(timing(true, false, false), &events), [(100, Gesture::Short), (200, Gesture::Short)]);
+    }
+
+    #[test]
+    fn stray_release_is_ignored() {
+        assert_eq!(replay(timing(true, true, true), &[(0, Edge::Release)]), []);
+    }
+
+    #[test]
+    fn burst_of_reports_is_one_press() {
+        // The Jabra repeats its report every few ms while the button is down, and sends no release
+        let gestures = replay(timing(false, false, false), &burst(0, 60, 20));
+        assert_eq!(gestures, [(60 + DEBOUNCE, Gesture::Short)]);
+        let gestures = replay(timing(false, true, false), &burst(0, 0, 20));
+        assert_eq!(gestures, [(DEBOUNCE, Gesture::Short)]);
+    }
+
+    #[test]
+    fn long_burst_is_a_long_press() {
+        let gestures = replay(timing(false, true, true), &burst(0, 2000, 30));
+        assert_eq!(gestures, [(LONG_PRESS, Gesture::Long)]);
+        // Without a long press action, it's just a long short press
+        let gestures = replay(timing(false, false, false), &burst(0, 2000, 40));
+        assert_eq!(gestures, [(2000 + DEBOUNCE, Gesture::Short)]);
+    }
+
+    #[test]
+    fn two_bursts_are_a_double_press() {
+        let mut events = burst(0, 40, 20);
+        events.extend(burst(200, 240, 20));
+        assert_eq!(replay(timing(false, true, true), &events), [(200, Gesture::Double)]);
+        // Separated by less than the debounce, they're one press
+        let mut events = burst(0, 40, 20);
+        events.extend(burst(80, 120, 20));
+        assert_eq!(replay(timing(false, false, true), &events), [(120 + DEBOUNCE + DOUBLE_PRESS, Gesture::Short)]);
+    }
+
+    #[test]
+    fn deadlines() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut detector = GestureDetector::new(timing(true, true, true));
+        assert_eq!(detector.next_deadline(), None);
+        detector.input(Edge::Press, at(0));
+        assert_eq!(detector.next_deadline(), Some(at(LONG_PRESS)));
+        assert_eq!(detector.poll(at(LONG_PRESS - 1)), None);
+        assert_eq!(detector.poll(at(LONG_PRESS)), Some(Gesture::Long));
+        // Nothing more to wait for until it's released
+        assert_eq!(detector.next_deadline(), None);
+        assert_eq!(detector.input(Edge::Release, at(2000)), None);
+        assert_eq!(detector.next_deadline(), None);
+
+        let mut detector = GestureDetector::new(timing(true, false, true));
+        detector.input(Edge::Press, at(0));
+        assert_eq!(detector.next_deadline(), None);
+        detector.input(Edge::Release, at(100));
+        assert_eq!(detector.next_deadline(), Some(at(100 + DOUBLE_PRESS)));
+        // Late polls still fire
+        assert_eq!(detector.poll(at(10_000)), Some(Gesture::Short));
+        assert_eq!(detector.next_deadline(), None);
+
+        // Without releases, the end of the burst comes first
+        let mut detector = GestureDetector::new(timing(false, true, false));
+        detector.input(Edge::Press, at(0));
+        assert_eq!(detector.next_deadline(), Some(at(DEBOUNCE)));
+        detector.input(Edge::Press, at(30));
+        assert_eq!(detector.next_deadline(), Some(at(30 + DEBOUNCE)));
+        assert_eq!(detector.poll(at(30 + DEBOUNCE)), Some(Gesture::Short));
+        assert_eq!(detector.next_deadline(), None);
+    }
+}
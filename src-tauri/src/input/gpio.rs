@@ -0,0 +1,279 @@
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::gestures::Edge;
+use super::{run_bindings, Binding, EdgeSender};
+use crate::config::{GpioBias, GpioConfig, GpioEdge};
+use crate::controls::Controller;
+use crate::metrics;
+
+// How long to wait before trying the line again, e.g. when something else holds it
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+// The GPIO character device uAPI, v2 (linux/gpio.h). The kernel lays these out the same on
+// 32 and 64 bit, which the size checks below make sure of.
+const GPIO_V2_LINES_MAX: usize = 64;
+const GPIO_V2_LINE_NUM_ATTRS_MAX: usize = 10;
+
+const GPIO_V2_LINE_FLAG_INPUT: u64 = 1 << 2;
+const GPIO_V2_LINE_FLAG_EDGE_RISING: u64 = 1 << 4;
+const GPIO_V2_LINE_FLAG_EDGE_FALLING: u64 = 1 << 5;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_UP: u64 = 1 << 8;
+const GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN: u64 = 1 << 9;
+const GPIO_V2_LINE_FLAG_BIAS_DISABLED: u64 = 1 << 10;
+
+const GPIO_V2_LINE_ATTR_ID_DEBOUNCE: u32 = 3;
+
+const GPIO_V2_LINE_EVENT_RISING_EDGE: u32 = 1;
+const GPIO_V2_LINE_EVENT_FALLING_EDGE: u32 = 2;
+
+#[repr(C)]
+#[derive(Default)]
+struct LineAttribute {
+    id: u32,
+    padding: u32,
+    value: u64, // A union in the kernel: flags, output values, or debounce_period_us in the low 32 bits
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct LineConfigAttribute {
+    attr: LineAttribute,
+    mask: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct LineConfig {
+    flags: u64,
+    num_attrs: u32,
+    padding: [u32; 5],
+    attrs: [LineConfigAttribute; GPIO_V2_LINE_NUM_ATTRS_MAX],
+}
+
+#[repr(C)]
+struct LineRequest {
+    offsets: [u32; GPIO_V2_LINES_MAX],
+    consumer: [u8; 32],
+    config: LineConfig,
+    num_lines: u32,
+    event_buffer_size: u32,
+    padding: [u32; 5],
+    fd: i32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct LineEvent {
+    timestamp_ns: u64,
+    id: u32,
+    offset: u32,
+    seqno: u32,
+    line_seqno: u32,
+    padding: [u32; 6],
+}
+
+const _: () = assert!(std::mem::size_of::<LineRequest>() == 592);
+const _: () = assert!(std::mem::size_of::<LineEvent>() == 48);
+
+// _IOWR(0xB4, 0x07, struct gpio_v2_line_request)
+const GPIO_V2_GET_LINE_IOCTL: libc::c_ulong = (3 << 30) | ((std::mem::size_of::<LineRequest>() as libc::c_ulong) << 16) | (0xB4 << 8) | 0x07;
+
+fn chip_path(chip: &str) -> String {
+    if chip.starts_with('/') {
+        chip.to_string()
+    } else {
+        format!("/dev/{}", chip)
+    }
+}
+
+// Asks the kernel for edge events on the line, debounced by the kernel itself.
+// Returns the line's file, which events are read from.
+fn request_line(config: &GpioConfig, line: u32) -> std::io::Result<File> {
+    let chip = OpenOptions::new().read(true).write(true).open(chip_path(&config.chip))?;
+
+    let mut flags = GPIO_V2_LINE_FLAG_INPUT | GPIO_V2_LINE_FLAG_EDGE_RISING | GPIO_V2_LINE_FLAG_EDGE_FALLING;
+    if !config.release {
+        flags &= !match config.edge {
+            GpioEdge::Falling => GPIO_V2_LINE_FLAG_EDGE_RISING,
+            GpioEdge::Rising => GPIO_V2_LINE_FLAG_EDGE_FALLING,
+        };
+    }
+    flags |= match config.bias {
+        GpioBias::PullUp => GPIO_V2_LINE_FLAG_BIAS_PULL_UP,
+        GpioBias::PullDown => GPIO_V2_LINE_FLAG_BIAS_PULL_DOWN,
+        GpioBias::Disabled => GPIO_V2_LINE_FLAG_BIAS_DISABLED,
+        GpioBias::AsIs => 0,
+    };
+
+    let mut request = LineRequest {
+        offsets: [0; GPIO_V2_LINES_MAX],
+        consumer: [0; 32],
+        config: LineConfig { flags, ..LineConfig::default() },
+        num_lines: 1,
+        event_buffer_size: 0,
+        padding: [0; 5],
+        fd: -1,
+    };
+    request.offsets[0] = line;
+    let consumer = b"rctv-kiosk";
+    request.consumer[..consumer.len()].copy_from_slice(consumer);
+    if config.debounce_ms > 0 {
+        request.config.num_attrs = 1;
+        request.config.attrs[0] = LineConfigAttribute {
+            attr: LineAttribute { id: GPIO_V2_LINE_ATTR_ID_DEBOUNCE, padding: 0, value: config.debounce_ms * 1000 },
+            mask: 1, // Applies to the first (only) requested line
+        };
+    }
+
+    // SAFETY: `request` matches struct gpio_v2_line_request and outlives the call
+    if unsafe { libc::ioctl(chip.as_raw_fd(), GPIO_V2_GET_LINE_IOCTL as _, &mut request as *mut LineRequest) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: the kernel just handed us this fd, and nothing else owns it
+    Ok(unsafe { File::from_raw_fd(request.fd) })
+}
+
+// Which way the button went, depending on how it's wired. None for events that aren't edges.
+fn decode_event(event_id: u32, wiring: GpioEdge) -> Option<Edge> {
+    match (event_id, wiring) {
+        (GPIO_V2_LINE_EVENT_FALLING_EDGE, GpioEdge::Falling) | (GPIO_V2_LINE_EVENT_RISING_EDGE, GpioEdge::Rising) => Some(Edge::Press),
+        (GPIO_V2_LINE_EVENT_FALLING_EDGE | GPIO_V2_LINE_EVENT_RISING_EDGE, _) => Some(Edge::Release),
+        _ => None,
+    }
+}
+
+// Sends the line's edges as presses and releases, until reading fails. `line` is the line's
+// file from `request_line`, or anything else handing out gpio_v2_line_event structs.
+fn read_events(mut line: impl Read, config: &GpioConfig, sender: &EdgeSender) -> std::io::Result<()> {
+    let mut buffer = [0u8; std::mem::size_of::<LineEvent>()];
+    loop {
+        line.read_exact(&mut buffer)?;
+        // SAFETY: LineEvent is plain old data, and the kernel hands out whole events
+        let event: LineEvent = unsafe { std::ptr::read_unaligned(buffer.as_ptr().cast()) };
+        let Some(edge) = decode_event(event.id, config.edge) else { continue };
+        metrics::INPUT_EVENTS.with_label_values(&["gpio"]).inc();
+        if sender.send(Ok((0, edge))).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// A single button on a GPIO line. Works the same with gpio-sim chips, for testing without a Pi.
+pub async fn start_gpio_controller(controller: Controller, line: u32) {
+    let config = controller.config.gpio.clone();
+    let binding = Binding {
+        label: format!("{} line {}", config.chip, line),
+        action: config.action,
+        long_press: config.long_press,
+        double_press: config.double_press,
+        has_release: config.release,
+    };
+
+    let mut last_error = None;
+    loop {
+        match request_line(&config, line) {
+            Ok(file) => {
+                info!(target: "hid", "GPIO button ready on {}", binding.label);
+                last_error = None;
+                let (sender, receiver): (EdgeSender, _) = tokio::sync::mpsc::unbounded_channel();
+                let thread_config = config.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = read_events(file, &thread_config, &sender) {
+                        let _ = sender.send(Err(e));
+                    }
+                });
+                if let Err(e) = run_bindings(&controller, std::slice::from_ref(&binding), receiver).await {
+                    warn!(target: "hid", "Lost GPIO button on {}: {}", binding.label, e);
+                }
+            }
+            Err(e) => {
+                let message = e.to_string();
+                if last_error.as_ref() != Some(&message) {
+                    warn!(target: "hid", "Could not get GPIO {}: {}, retrying every {} seconds", binding.label, message, RETRY_INTERVAL.as_secs());
+                }
+                last_error = Some(message);
+            }
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A gpio_v2_line_event as the kernel writes it
+    fn event_bytes(id: u32, seqno: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(seqno as u64 * 1_000_000).to_ne_bytes());
+        bytes.extend_from_slice(&id.to_ne_bytes());
+        bytes.extend_from_slice(&17u32.to_ne_bytes());
+        bytes.extend_from_slice(&seqno.to_ne_bytes());
+        bytes.extend_from_slice(&seqno.to_ne_bytes());
+        bytes.extend_from_slice(&[0u8; 24]);
+        assert_eq!(bytes.len(), std::mem::size_of::<LineEvent>());
+        bytes
+    }
+
+    // Runs `read_events` over a fake line, returning the edges sent and how reading ended
+    fn read_fake_line(events: &[u32], edge: GpioEdge) -> (Vec<Edge>, std::io::ErrorKind) {
+        let line: Vec<u8> = events.iter().enumerate().flat_map(|(seqno, &id)| event_bytes(id, seqno as u32)).collect();
+        let config = GpioConfig { edge, ..GpioConfig::default() };
+        let (sender, mut receiver): (EdgeSender, _) = tokio::sync::mpsc::unbounded_channel();
+        let end = read_events(std::io::Cursor::new(line), &config, &sender).unwrap_err().kind();
+        let mut edges = Vec::new();
+        while let Ok(sent) = receiver.try_recv() {
+            let (binding, edge) = sent.unwrap();
+            assert_eq!(binding, 0);
+            edges.push(edge);
+        }
+        (edges, end)
+    }
+
+    #[test]
+    fn decodes_edges_for_a_button_to_ground() {
+        let events = [GPIO_V2_LINE_EVENT_FALLING_EDGE, GPIO_V2_LINE_EVENT_RISING_EDGE];
+        let (edges, end) = read_fake_line(&events, GpioEdge::Falling);
+        assert_eq!(edges, [Edge::Press, Edge::Release]);
+        // The fake line runs out, a real one doesn't
+        assert_eq!(end, std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn decodes_edges_for_a_button_to_3v3() {
+        let events = [GPIO_V2_LINE_EVENT_RISING_EDGE, GPIO_V2_LINE_EVENT_FALLING_EDGE, GPIO_V2_LINE_EVENT_RISING_EDGE];
+        let (edges, _) = read_fake_line(&events, GpioEdge::Rising);
+        assert_eq!(edges, [Edge::Press, Edge::Release, Edge::Press]);
+    }
+
+    #[test]
+    fn skips_unknown_events() {
+        let events = [0, GPIO_V2_LINE_EVENT_FALLING_EDGE, 7, GPIO_V2_LINE_EVENT_RISING_EDGE];
+        let (edges, _) = read_fake_line(&events, GpioEdge::Falling);
+        assert_eq!(edges, [Edge::Press, Edge::Release]);
+        assert_eq!(decode_event(3, GpioEdge::Rising), None);
+    }
+
+    #[test]
+    fn stops_on_a_partial_event() {
+        let mut line = event_bytes(GPIO_V2_LINE_EVENT_FALLING_EDGE, 0);
+        line.extend_from_slice(&event_bytes(GPIO_V2_LINE_EVENT_RISING_EDGE, 1)[..20]);
+        let (sender, mut receiver): (EdgeSender, _) = tokio::sync::mpsc::unbounded_channel();
+        let result = read_events(std::io::Cursor::new(line), &GpioConfig::default(), &sender);
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(receiver.try_recv().unwrap().unwrap(), (0, Edge::Press));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn builds_the_line_request_ioctl() {
+        // _IOWR(0xB4, 0x07, struct gpio_v2_line_request), as linux/gpio.h computes it
+        assert_eq!(GPIO_V2_GET_LINE_IOCTL, 0xC250_B407);
+        assert_eq!(chip_path("gpiochip4"), "/dev/gpiochip4");
+        assert_eq!(chip_path("/dev/gpiochip0"), "/dev/gpiochip0");
+    }
+}
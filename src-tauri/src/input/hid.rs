@@ -0,0 +1,294 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{debug, info};
+
+use super::gestures::Edge;
+use super::{run_bindings, select_device, sort_devices, Binding, EdgeSender, InputDevice};
+use crate::config::HidConfig;
+use crate::controls::Controller;
+use crate::metrics;
+
+// Biggest report the kernel hands out (HID_MAX_BUFFER_SIZE)
+const MAX_REPORT_SIZE: usize = 16384;
+
+// How often to look for the device again while it's not there
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+// Every hidraw device, by number
+pub fn list_devices() -> Vec<InputDevice> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/hidraw") else { return Vec::new() };
+    let mut devices: Vec<InputDevice> = entries
+        .filter_map(|entry| read_device(&entry.ok()?.file_name().to_string_lossy()))
+        .collect();
+    sort_devices(&mut devices);
+    devices
+}
+
+// From the uevent of the HID device behind /sys/class/hidraw/<node>:
+// HID_ID=0003:00000B0E:00000422, HID_NAME=..., HID_PHYS=..., HID_UNIQ=<serial>
+fn read_device(node: &str) -> Option<InputDevice> {
+    let uevent = std::fs::read_to_string(format!("/sys/class/hidraw/{}/device/uevent", node)).ok()?;
+    let mut device = InputDevice {
+        path: PathBuf::from("/dev").join(node),
+        name: String::new(),
+        vendor_id: 0,
+        product_id: 0,
+        serial: String::new(),
+        phys: String::new(),
+    };
+    for line in uevent.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        match key {
+            "HID_ID" => {
+                let mut ids = value.split(':').skip(1).map(|id| u32::from_str_radix(id, 16).unwrap_or(0) as u16);
+                device.vendor_id = ids.next().unwrap_or(0);
+                device.product_id = ids.next().unwrap_or(0);
+            }
+            "HID_NAME" => device.name = value.to_string(),
+            "HID_PHYS" => device.phys = value.to_string(),
+            "HID_UNIQ" => device.serial = value.to_string(),
+            _ => {}
+        }
+    }
+    Some(device)
+}
+
+// A report the way hid-recorder prints it, which is also how button patterns are written:
+// length in decimal, then the bytes in hex. E.g. "3 03 01 00"
+pub fn format_report(report: &[u8]) -> String {
+    let mut formatted = report.len().to_string();
+    for byte in report {
+        formatted.push_str(&format!(" {:02x}", byte));
+    }
+    formatted
+}
+
+// Where reports come from: a hidraw device, or a hid-recorder recording when testing
+pub enum ReportSource {
+    Device(File),
+    Recording { reader: Box<dyn BufRead + Send>, last_timestamp: Option<f64> },
+}
+
+impl ReportSource {
+    pub fn open_device(device: &InputDevice) -> std::io::Result<ReportSource> {
+        Ok(ReportSource::Device(File::open(&device.path)?))
+    }
+
+    // A file or a named pipe with hid-recorder output. Only the `E:` lines are used, and they
+    // are played back with their original timing.
+    pub fn open_recording(path: &str) -> std::io::Result<ReportSource> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(ReportSource::Recording { reader: Box::new(reader), last_timestamp: None })
+    }
+
+    // Blocks until the next report. None once the device is gone or the recording is over.
+    pub fn next_report(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        match self {
+            // Each read returns exactly one report
+            ReportSource::Device(file) => {
+                let mut buffer = vec![0u8; MAX_REPORT_SIZE];
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                buffer.truncate(read);
+                Ok(Some(buffer))
+            }
+            ReportSource::Recording { reader, last_timestamp } => {
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Ok(None);
+                    }
+                    let Some((timestamp, report)) = parse_recorded_event(&line) else { continue };
+                    if let Some(last) = last_timestamp.replace(timestamp) {
+                        std::thread::sleep(Duration::from_secs_f64((timestamp - last).max(0.0)));
+                    }
+                    return Ok(Some(report));
+                }
+            }
+        }
+    }
+}
+
+// `E: 000012.345678 3 03 01 00` is a 3 byte report, 12.3 seconds into the recording
+fn parse_recorded_event(line: &str) -> Option<(f64, Vec<u8>)> {
+    let mut fields = line.trim().strip_prefix("E:")?.split_whitespace();
+    // f64 also parses "inf" and "NaN", which can't be waited for
+    let timestamp = fields.next()?.parse().ok().filter(|t: &f64| t.is_finite() && *t >= 0.0)?;
+    let length: usize = fields.next()?.parse().ok()?;
+    let report = fields.map(|byte| u8::from_str_radix(byte, 16)).collect::<Result<Vec<u8>, _>>().ok()?;
+    (report.len() == length).then_some((timestamp, report))
+}
+
+// Whether we're listening to a device, for logs and the status report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum HidConnection {
+    Searching(String), // Why no device is in use yet
+    Connected(String), // The device, as displayed by InputDevice
+    Replaying(String), // The recording's path
+}
+
+impl Default for HidConnection {
+    fn default() -> Self {
+        HidConnection::Searching("not started yet".to_string())
+    }
+}
+
+fn set_connection(controller: &Controller, connection: HidConnection) {
+    let mut current = controller.hid_connection.lock().unwrap();
+    if *current != connection {
+        match &connection {
+            HidConnection::Searching(reason) => info!(target: "hid", "Waiting for a HID device: {}", reason),
+            HidConnection::Connected(device) => info!(target: "hid", "HID device connected: {}", device),
+            HidConnection::Replaying(path) => info!(target: "hid", "Reading HID reports from recording {}", path),
+        }
+        *current = connection;
+    }
+}
+
+fn open_matching_device(config: &HidConfig) -> Result<(InputDevice, ReportSource), String> {
+    let devices = list_devices();
+    let device = select_device(&config.devices, &devices).map_err(|e| format!("`hid.devices`: {}", e))?;
+    let source = ReportSource::open_device(device).map_err(|e| format!("could not open {}: {}", device, e))?;
+    Ok((device.clone(), source))
+}
+
+fn bindings(config: &HidConfig) -> Vec<Binding> {
+    config
+        .buttons
+        .iter()
+        .map(|button| Binding {
+            label: button.report.clone(),
+            action: button.action,
+            long_press: button.long_press,
+            double_press: button.double_press,
+            has_release: button.release.is_some(),
+        })
+        .collect()
+}
+
+// Hands button gestures to the controller until the device goes away or the recording ends.
+// Reports are read on a thread of their own, so gesture timers (long press...) run in between.
+async fn read_reports(controller: &Controller, mut source: ReportSource) -> std::io::Result<()> {
+    let (sender, receiver): (EdgeSender, _) = tokio::sync::mpsc::unbounded_channel();
+    let buttons = controller.config.hid.buttons.clone();
+    std::thread::spawn(move || loop {
+        let report = match source.next_report() {
+            Ok(Some(report)) => {
+                metrics::INPUT_EVENTS.with_label_values(&["hid"]).inc();
+                format_report(&report)
+            }
+            Ok(None) => break,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                break;
+            }
+        };
+        match buttons.iter().position(|button| button.is_press(&report) || button.is_release(&report)) {
+            Some(index) => {
+                let edge = if buttons[index].is_press(&report) { Edge::Press } else { Edge::Release };
+                if sender.send(Ok((index, edge))).is_err() {
+                    break;
+                }
+            }
+            // Helps finding the patterns for other buttons
+            None => debug!(target: "hid", "Unmapped HID report: {}", report),
+        }
+    });
+
+    run_bindings(controller, &bindings(&controller.config.hid), receiver).await
+}
+
+// Runs for as long as the app does: the device may be plugged in after we start, and
+// unplugged and plugged back in any number of times. Sysfs is rescanned every
+// RESCAN_INTERVAL while there is no device.
+pub async fn start_hid_controller(controller: Controller) -> std::io::Result<()> {
+    info!(target: "hid", "HID controller started, monitoring for reports:");
+    for button in &controller.config.hid.buttons {
+        debug!(target: "hid", "  {} -> {:?} (long press: {:?}, double press: {:?})", button.report, button.action, button.long_press, button.double_press);
+    }
+
+    if let Some(recording) = &controller.config.hid.recording {
+        let source = ReportSource::open_recording(recording)?;
+        set_connection(&controller, HidConnection::Replaying(recording.clone()));
+        read_reports(&controller, source).await?;
+        info!(target: "hid", "HID recording ended");
+        return Ok(());
+    }
+
+    loop {
+        match open_matching_device(&controller.config.hid) {
+            Ok((device, source)) => {
+                set_connection(&controller, HidConnection::Connected(device.to_string()));
+                let reason = match read_reports(&controller, source).await {
+                    Ok(()) => "device closed".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                info!(target: "hid", "HID device {} disconnected: {}", device.path.display(), reason);
+                set_connection(&controller, HidConnection::Searching(format!("{} disconnected", device.path.display())));
+            }
+            Err(e) => set_connection(&controller, HidConnection::Searching(e)),
+        }
+        tokio::time::sleep(RESCAN_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn formats_reports_like_hid_recorder() {
+        assert_eq!(format_report(&[0x03, 0x01, 0x00]), "3 03 01 00");
+        assert_eq!(format_report(&[0x02, 0xab, 0xff]), "3 02 ab ff");
+        assert_eq!(format_report(&[]), "0");
+    }
+
+    #[test]
+    fn parses_recorded_events() {
+        assert_eq!(parse_recorded_event("E: 000000.000000 3 03 01 00"), Some((0.0, vec![0x03, 0x01, 0x00])));
+        assert_eq!(parse_recorded_event("E: 000012.345678 3 03 00 00\n"), Some((12.345678, vec![0x03, 0x00, 0x00])));
+        assert_eq!(parse_recorded_event("E: 000001.500000 2 02 AB"), Some((1.5, vec![0x02, 0xab])));
+    }
+
+    #[test]
+    fn skips_malformed_and_other_lines() {
+        // The rest of hid-recorder's output: device info and the report descriptor
+        assert_eq!(parse_recorded_event("D: 0"), None);
+        assert_eq!(parse_recorded_event("N: GN Audio A/S Jabra SPEAK 510 USB"), None);
+        assert_eq!(parse_recorded_event("R: 101 05 0b 09 05 a1 01"), None);
+        assert_eq!(parse_recorded_event("# Report descriptor:"), None);
+        assert_eq!(parse_recorded_event(""), None);
+        // Length not matching the bytes
+        assert_eq!(parse_recorded_event("E: 000000.000000 4 03 01 00"), None);
+        assert_eq!(parse_recorded_event("E: 000000.000000 2 03 01 00"), None);
+        // Not hex, not a timestamp, cut short
+        assert_eq!(parse_recorded_event("E: 000000.000000 3 03 0g 00"), None);
+        assert_eq!(parse_recorded_event("E: now 3 03 01 00"), None);
+        // Timestamps that can't be slept until
+        assert_eq!(parse_recorded_event("E: inf 3 03 01 00"), None);
+        assert_eq!(parse_recorded_event("E: NaN 3 03 01 00"), None);
+        assert_eq!(parse_recorded_event("E: -000001.000000 3 03 01 00"), None);
+        assert_eq!(parse_recorded_event("E: 000000.000000"), None);
+    }
+
+    #[test]
+    fn replays_recordings() {
+        let recording = "D: 0\n\
+                         E: 000000.000000 3 03 01 00\n\
+                         garbage\n\
+                         E: 000000.001000 3 03 00 00\n";
+        let mut source = ReportSource::Recording { reader: Box::new(Cursor::new(recording)), last_timestamp: None };
+        assert_eq!(source.next_report().unwrap(), Some(vec![0x03, 0x01, 0x00]));
+        assert_eq!(source.next_report().unwrap(), Some(vec![0x03, 0x00, 0x00]));
+        assert_eq!(source.next_report().unwrap(), None);
+    }
+}
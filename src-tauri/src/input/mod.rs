@@ -0,0 +1,262 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::config::{Action, DeviceMatch};
+use crate::controls::Controller;
+
+mod evdev;
+mod gestures;
+mod gpio;
+mod hid;
+
+pub use evdev::key_code;
+pub use hid::HidConnection;
+use gestures::{Edge, Gesture, GestureDetector, GestureTiming};
+
+// A device an input backend reads from (/dev/hidrawN, /dev/input/eventN), described from sysfs
+#[derive(Debug, Clone)]
+pub struct InputDevice {
+    pub path: PathBuf,
+    pub name: String,      // e.g. "GN Netcom A/S Jabra SPEAK 510 USB"
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: String,    // Empty for most devices
+    pub phys: String,      // Where it's plugged in, e.g. "usb-0000:01:00.0-1.3/input3"
+}
+
+impl fmt::Display for InputDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:04x}:{:04x} \"{}\"", self.path.display(), self.vendor_id, self.product_id, self.name)?;
+        if !self.serial.is_empty() {
+            write!(f, " serial {}", self.serial)?;
+        }
+        write!(f, " at {}", self.phys)
+    }
+}
+
+// By device number, so /dev/hidraw2 comes before /dev/hidraw10
+pub fn sort_devices(devices: &mut [InputDevice]) {
+    devices.sort_by_cached_key(|device| {
+        let path = device.path.to_string_lossy();
+        let prefix = path.trim_end_matches(|c: char| c.is_ascii_digit());
+        (prefix.to_string(), path[prefix.len()..].parse::<u64>().ok())
+    });
+}
+
+fn device_matches(rule: &DeviceMatch, device: &InputDevice) -> bool {
+    if rule.vendor_product().is_some_and(|id| id != (device.vendor_id, device.product_id)) {
+        return false;
+    }
+    if let Some(Ok(name)) = rule.name_regex() {
+        if !name.is_match(&device.name) {
+            return false;
+        }
+    }
+    if rule.serial.as_ref().is_some_and(|serial| *serial != device.serial) {
+        return false;
+    }
+    if rule.usb_path.as_ref().is_some_and(|usb_path| !device.phys.starts_with(usb_path.as_str())) {
+        return false;
+    }
+    true
+}
+
+// Rules are tried in order, so an earlier rule wins even over a device listed first
+pub fn select_device<'a>(rules: &[DeviceMatch], devices: &'a [InputDevice]) -> Result<&'a InputDevice, String> {
+    rules
+        .iter()
+        .find_map(|rule| devices.iter().find(|device| device_matches(rule, device)))
+        .ok_or_else(|| {
+            let mut message = String::from("no device matches the rules. ");
+            if devices.is_empty() {
+                message.push_str("No devices found");
+            } else {
+                message.push_str("Found:");
+                for device in devices {
+                    message.push_str(&format!("\n  {}", device));
+                }
+            }
+            message
+        })
+}
+
+// A button (HID report, key...) of some input and what its gestures do
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub label: String, // For the logs
+    pub action: Action,
+    pub long_press: Option<Action>,
+    pub double_press: Option<Action>,
+    pub has_release: bool,
+}
+
+impl Binding {
+    fn action_for(&self, gesture: Gesture) -> Option<Action> {
+        match gesture {
+            Gesture::Short => Some(self.action),
+            Gesture::Long => self.long_press,
+            Gesture::Double => self.double_press,
+        }
+    }
+}
+
+// What backends send to `run_bindings`: which binding went down or up. An error ends the run.
+pub type EdgeSender = mpsc::UnboundedSender<std::io::Result<(usize, Edge)>>;
+pub type EdgeReceiver = mpsc::UnboundedReceiver<std::io::Result<(usize, Edge)>>;
+
+// Turns the edges of every binding into gestures and hands their actions to the controller,
+// until all senders are gone or one of them reports an error
+pub async fn run_bindings(controller: &Controller, bindings: &[Binding], mut edges: EdgeReceiver) -> std::io::Result<()> {
+    let mut detectors: Vec<GestureDetector> = bindings
+        .iter()
+        .map(|binding| {
+            let gestures = &controller.config.gestures;
+            GestureDetector::new(GestureTiming::new(gestures, binding.has_release, binding.long_press.is_some(), binding.double_press.is_some()))
+        })
+        .collect();
+
+    loop {
+        let deadline = detectors.iter().filter_map(GestureDetector::next_deadline).min();
+        let received = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), edges.recv()).await.ok(),
+            None => Some(edges.recv().await),
+        };
+
+        let now = Instant::now();
+        for (binding, detector) in bindings.iter().zip(detectors.iter_mut()) {
+            if let Some(gesture) = detector.poll(now) {
+                fire(controller, binding, gesture);
+            }
+        }
+
+        // Nothing received means a gesture timer ran out
+        let Some(received) = received else { continue };
+        let Some(edge) = received else { return Ok(()) };
+        let (index, edge) = edge?;
+        if let Some(gesture) = detectors[index].input(edge, now) {
+            fire(controller, &bindings[index], gesture);
+        }
+    }
+}
+
+fn fire(controller: &Controller, binding: &Binding, gesture: Gesture) {
+    if let Some(action) = binding.action_for(gesture) {
+        info!(target: "hid", "{:?} press on {}", gesture, binding.label);
+        controller.handle(action);
+    }
+}
+
+// Every configured input, each on a task of its own. Runs for as long as the app does.
+pub async fn start_inputs(controller: Controller) {
+    let mut tasks = Vec::new();
+
+    let hid = &controller.config.hid;
+    if !hid.devices.is_empty() || hid.recording.is_some() {
+        let hid_controller = controller.clone();
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = hid::start_hid_controller(hid_controller).await {
+                error!(target: "hid", "HID controller stopped: {}", e);
+            }
+        }));
+    }
+
+    if !controller.config.evdev.devices.is_empty() {
+        let evdev_controller = controller.clone();
+        tasks.push(tokio::spawn(evdev::start_evdev_controller(evdev_controller)));
+    }
+
+    if let Some(line) = controller.config.gpio.line {
+        let gpio_controller = controller.clone();
+        tasks.push(tokio::spawn(gpio::start_gpio_controller(gpio_controller, line)));
+    }
+
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(path: &str, id: (u16, u16), name: &str, serial: &str, phys: &str) -> InputDevice {
+        InputDevice {
+            path: PathBuf::from(path),
+            name: name.to_string(),
+            vendor_id: id.0,
+            product_id: id.1,
+            serial: serial.to_string(),
+            phys: phys.to_string(),
+        }
+    }
+
+    fn devices() -> Vec<InputDevice> {
+        let mut devices = vec![
+            device("/dev/hidraw10", (0x0b0e, 0x0422), "GN Netcom A/S Jabra SPEAK 510 USB", "B1", "usb-0000:01:00.0-1.4/input3"),
+            device("/dev/hidraw0", (0x046d, 0xc52b), "Logitech USB Receiver", "", "usb-0000:01:00.0-1.1/input2"),
+            device("/dev/hidraw2", (0x0b0e, 0x0422), "GN Netcom A/S Jabra SPEAK 510 USB", "A7", "usb-0000:01:00.0-1.3/input3"),
+        ];
+        sort_devices(&mut devices);
+        devices
+    }
+
+    fn rule(id: Option<&str>, name: Option<&str>, serial: Option<&str>, usb_path: Option<&str>) -> DeviceMatch {
+        DeviceMatch {
+            id: id.map(str::to_string),
+            name: name.map(str::to_string),
+            serial: serial.map(str::to_string),
+            usb_path: usb_path.map(str::to_string),
+        }
+    }
+
+    fn selected(rules: &[DeviceMatch]) -> Result<String, String> {
+        let devices = devices();
+        select_device(rules, &devices).map(|device| device.path.display().to_string())
+    }
+
+    #[test]
+    fn sorts_devices_by_number() {
+        let paths: Vec<_> = devices().iter().map(|device| device.path.display().to_string()).collect();
+        assert_eq!(paths, ["/dev/hidraw0", "/dev/hidraw2", "/dev/hidraw10"]);
+    }
+
+    #[test]
+    fn matches_every_field_that_is_set() {
+        let jabra = &devices()[1];
+        assert!(device_matches(&rule(Some("0b0e:0422"), None, None, None), jabra));
+        assert!(device_matches(&rule(Some("0B0E:0422"), Some("(?i)jabra"), Some("A7"), Some("usb-0000:01:00.0-1.3")), jabra));
+        assert!(!device_matches(&rule(Some("0b0e:0423"), None, None, None), jabra));
+        assert!(!device_matches(&rule(Some("0b0e:0422"), Some("^Logitech"), None, None), jabra));
+        assert!(!device_matches(&rule(None, Some("jabra"), None, None), jabra)); // Case sensitive
+        assert!(!device_matches(&rule(None, None, Some("B1"), None), jabra));
+        assert!(!device_matches(&rule(None, None, None, Some("usb-0000:01:00.0-1.4")), jabra));
+    }
+
+    #[test]
+    fn picks_the_lowest_numbered_match() {
+        // Both Jabras match: hidraw2 comes before hidraw10
+        assert_eq!(selected(&[rule(Some("0b0e:0422"), None, None, None)]), Ok("/dev/hidraw2".to_string()));
+        assert_eq!(selected(&[rule(None, Some("Jabra SPEAK \\d+"), None, None)]), Ok("/dev/hidraw2".to_string()));
+        assert_eq!(selected(&[rule(None, None, Some("B1"), None)]), Ok("/dev/hidraw10".to_string()));
+    }
+
+    #[test]
+    fn tries_rules_in_order() {
+        let rules = [rule(None, None, None, Some("usb-0000:01:00.0-1.4")), rule(Some("046d:c52b"), None, None, None)];
+        assert_eq!(selected(&rules), Ok("/dev/hidraw10".to_string()));
+        let rules = [rule(Some("dead:beef"), None, None, None), rule(Some("046d:c52b"), None, None, None)];
+        assert_eq!(selected(&rules), Ok("/dev/hidraw0".to_string()));
+    }
+
+    #[test]
+    fn lists_the_devices_when_nothing_matches() {
+        let error = selected(&[rule(Some("dead:beef"), None, None, None)]).unwrap_err();
+        assert!(error.contains("/dev/hidraw0 046d:c52b \"Logitech USB Receiver\" at usb-0000:01:00.0-1.1/input2"), "{}", error);
+        assert!(error.contains("serial A7"), "{}", error);
+        let error = select_device(&[rule(Some("dead:beef"), None, None, None)], &[]).unwrap_err();
+        assert!(error.ends_with("No devices found"), "{}", error);
+    }
+}
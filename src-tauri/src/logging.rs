@@ -0,0 +1,71 @@
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tracing::warn;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::config::LoggingConfig;
+use crate::heartbeat::ErrorCollector;
+
+// Lets the control API change the filter while running
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+pub fn check_filter(filter: &str) -> Result<(), String> {
+    EnvFilter::try_new(filter).map(|_| ()).map_err(|e| format!("`{}` is not a log filter: {}", filter, e))
+}
+
+fn open_log_file(config: &LoggingConfig, dir: &Path) -> Result<RollingFileAppender, String> {
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("rctv")
+        .filename_suffix("log")
+        .max_log_files(config.max_files)
+        .build(dir)
+        .map_err(|e| format!("could not open a log file in {}: {}", dir.display(), e))
+}
+
+// Logs to stdout, which systemd puts in the journal, and to a file rotated daily in `log_dir`
+// if there is one. RUST_LOG, when set, wins over `logging.filter`. Called once, at startup.
+pub fn init(config: &LoggingConfig, log_dir: Option<&Path>) {
+    let directives = std::env::var("RUST_LOG").ok().filter(|value| !value.is_empty()).unwrap_or_else(|| config.filter.clone());
+    // Nothing to log the problems to yet, they're logged once we're set up
+    let mut problems = Vec::new();
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+        problems.push(format!("Invalid log filter `{}` ({}), using `info`", directives, e));
+        EnvFilter::new("info")
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+
+    // No colors in the journal
+    let ansi = std::io::stdout().is_terminal();
+    let stdout = if config.json { fmt::layer().json().boxed() } else { fmt::layer().with_ansi(ansi).boxed() };
+    let file = match log_dir.filter(|_| config.file).map(|dir| open_log_file(config, dir)) {
+        Some(Ok(appender)) if config.json => Some(fmt::layer().json().with_writer(appender).boxed()),
+        Some(Ok(appender)) => Some(fmt::layer().with_ansi(false).with_writer(appender).boxed()),
+        Some(Err(e)) => {
+            problems.push(format!("Logging to stdout only: {}", e));
+            None
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry().with(filter).with(stdout).with(file).with(ErrorCollector).init();
+    let _ = FILTER.set(handle);
+    for problem in problems {
+        warn!(target: "process", "{}", problem);
+    }
+}
+
+pub fn current_filter() -> Option<String> {
+    FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+// Until the next restart, which goes back to the configured filter
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives).map_err(|e| format!("`{}` is not a log filter: {}", directives, e))?;
+    let handle = FILTER.get().ok_or("logging is not set up")?;
+    handle.reload(filter).map_err(|e| e.to_string())
+}
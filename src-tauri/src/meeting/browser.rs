@@ -0,0 +1,404 @@
+use serde_json::Value;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use thirtyfour::prelude::*;
+use tracing::{debug, error, info, warn};
+
+use super::cdp::CdpPage;
+use super::ports;
+use super::webview::WebviewPage;
+use super::{poll_until, run_step, JoinDeadlines, MeetingError, MeetingStep};
+use crate::config::{BrowserBackend, BrowserConfig, MeetingConfig};
+use crate::metrics;
+
+#[derive(Debug)]
+pub struct BrowserError(String);
+
+impl BrowserError {
+    pub fn new(message: impl Into<String>) -> Self {
+        BrowserError(message.into())
+    }
+}
+
+impl std::fmt::Display for BrowserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BrowserError {}
+
+impl From<WebDriverError> for BrowserError {
+    fn from(e: WebDriverError) -> Self {
+        BrowserError(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for BrowserError {
+    fn from(e: reqwest::Error) -> Self {
+        BrowserError(e.to_string())
+    }
+}
+
+pub type BrowserResult<T> = Result<T, BrowserError>;
+
+pub async fn kill_chrome_processes() {
+    info!(target: "process", "Killing Chrome/Chromium and ChromeDriver processes...");
+    
+    // Kill ChromeDriver, then Chrome/Chromium
+    let patterns = ["chromedriver", "chromium", "chrome"];
+    for pattern in patterns {
+        kill_matching(pattern).await;
+    }
+    
+    // Wait for processes to die
+    let all_gone = || async {
+        for pattern in patterns {
+            if process_running(pattern).await {
+                return false;
+            }
+        }
+        true
+    };
+    if poll_until(PROCESS_EXIT_TIMEOUT, all_gone).await {
+        info!(target: "process", "Chrome processes killed");
+    } else {
+        warn!(target: "process", "Chrome processes still running after {:?}", PROCESS_EXIT_TIMEOUT);
+    }
+}
+
+const PROCESS_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Sends SIGTERM to every process whose command line matches `pattern`
+async fn kill_matching(pattern: &str) {
+    let _ = tokio::process::Command::new("pkill")
+        .arg("-f")
+        .arg(pattern)
+        .output()
+        .await;
+}
+
+async fn process_running(pattern: &str) -> bool {
+    tokio::process::Command::new("pgrep")
+        .arg("-f")
+        .arg(pattern)
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+// PIDs of the processes whose command line matches `pattern`, for the status report
+pub async fn process_ids(pattern: &str) -> Vec<u32> {
+    let Ok(output) = tokio::process::Command::new("pgrep").arg("-f").arg("--").arg(pattern).output().await else { return Vec::new() };
+    String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| line.trim().parse().ok()).collect()
+}
+
+// The browser the meeting runs in: Chromium, either directly over the DevTools protocol or
+// through ChromeDriver (the original setup, kept as a fallback), or a Tauri window of our own.
+pub enum Browser {
+    Cdp(CdpPage),
+    WebDriver(WebDriver),
+    Webview(WebviewPage),
+}
+
+// Finds the first visible element matching the XPath in `arguments[0]`, in the top document or
+// any same-origin iframe, and returns its center in top-level page coordinates. `arguments[1]`
+// scrolls it into view first, `arguments[2]` also clicks it from the script.
+const LOCATE_XPATH_SCRIPT: &str = r#"
+    const visible = (element) => {
+        const rect = element.getBoundingClientRect();
+        const style = element.ownerDocument.defaultView.getComputedStyle(element);
+        return rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden' && style.display !== 'none';
+    };
+    const frames = [null, ...document.querySelectorAll('iframe')];
+    for (const frame of frames) {
+        let doc;
+        try { doc = frame ? frame.contentDocument : document; } catch (e) { continue; }
+        if (!doc) continue;
+        const found = doc.evaluate(arguments[0], doc, null, XPathResult.ORDERED_NODE_SNAPSHOT_TYPE, null);
+        for (let i = 0; i < found.snapshotLength; i++) {
+            const element = found.snapshotItem(i);
+            if (element.nodeType !== Node.ELEMENT_NODE || !visible(element)) continue;
+            if (arguments[1]) element.scrollIntoView({ behavior: 'instant', block: 'center', inline: 'center' });
+            if (arguments[2]) element.click();
+            const rect = element.getBoundingClientRect();
+            const offset = frame ? frame.getBoundingClientRect() : { left: 0, top: 0 };
+            const border = frame ? { left: frame.clientLeft, top: frame.clientTop } : { left: 0, top: 0 };
+            return {
+                x: offset.left + border.left + rect.left + rect.width / 2,
+                y: offset.top + border.top + rect.top + rect.height / 2,
+            };
+        }
+    }
+    return null;
+"#;
+
+impl Browser {
+    pub async fn goto(&self, url: &str) -> BrowserResult<()> {
+        match self {
+            Browser::Cdp(page) => page.navigate(url).await,
+            Browser::WebDriver(driver) => Ok(driver.goto(url).await?),
+            Browser::Webview(page) => page.navigate(url).await,
+        }
+    }
+
+    // Runs `script` as a function body with `args` as its `arguments`, returning what it returns
+    pub async fn execute(&self, script: &str, args: Vec<Value>) -> BrowserResult<Value> {
+        match self {
+            Browser::Cdp(page) => page.execute(script, args).await,
+            Browser::WebDriver(driver) => Ok(driver.execute(script, args).await?.json().clone()),
+            Browser::Webview(page) => page.execute(script, args).await,
+        }
+    }
+
+    pub async fn current_url(&self) -> BrowserResult<String> {
+        match self {
+            Browser::Cdp(page) => Ok(page.execute("return location.href;", Vec::new()).await?.as_str().unwrap_or_default().to_string()),
+            Browser::WebDriver(driver) => Ok(driver.current_url().await?.to_string()),
+            Browser::Webview(page) => page.current_url(),
+        }
+    }
+
+    pub async fn title(&self) -> BrowserResult<String> {
+        Ok(self.execute("return document.title;", Vec::new()).await?.as_str().unwrap_or_default().to_string())
+    }
+
+    // HTML of the top document
+    pub async fn source(&self) -> BrowserResult<String> {
+        match self {
+            Browser::Cdp(_) | Browser::Webview(_) => Ok(self.execute("return document.documentElement.outerHTML;", Vec::new()).await?.as_str().unwrap_or_default().to_string()),
+            Browser::WebDriver(driver) => {
+                // A previous step may have left us inside an iframe
+                let _ = driver.enter_default_frame().await;
+                Ok(driver.source().await?)
+            }
+        }
+    }
+
+    pub async fn screenshot_png(&self) -> BrowserResult<Vec<u8>> {
+        match self {
+            Browser::Cdp(page) => page.screenshot_png().await,
+            Browser::WebDriver(driver) => Ok(driver.screenshot_as_png().await?),
+            Browser::Webview(_) => Err(BrowserError::new("screenshots are not supported in the meeting window")),
+        }
+    }
+
+    // Whether a visible element matches `xpath`, in the page or any same-origin iframe
+    pub async fn has_xpath(&self, xpath: &str) -> BrowserResult<bool> {
+        Ok(!self.execute(LOCATE_XPATH_SCRIPT, vec![serde_json::json!(xpath), serde_json::json!(false), serde_json::json!(false)]).await?.is_null())
+    }
+
+    // Clicks the first visible element matching `xpath`, in the page or any same-origin iframe.
+    // Returns false if there is none.
+    pub async fn click_xpath(&self, xpath: &str) -> BrowserResult<bool> {
+        match self {
+            Browser::Cdp(page) => {
+                let center = page.execute(LOCATE_XPATH_SCRIPT, vec![serde_json::json!(xpath), serde_json::json!(true), serde_json::json!(false)]).await?;
+                match (center["x"].as_f64(), center["y"].as_f64()) {
+                    (Some(x), Some(y)) => {
+                        page.click_at(x, y).await?;
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            }
+            Browser::WebDriver(driver) => {
+                let clicked = click_xpath_in_frames(driver, xpath).await;
+                let _ = driver.enter_default_frame().await;
+                clicked
+            },
+            // No way to send real input events to the webview, a script click has to do
+            Browser::Webview(page) => {
+                let args = vec![serde_json::json!(xpath), serde_json::json!(true), serde_json::json!(true)];
+                Ok(!page.execute(LOCATE_XPATH_SCRIPT, args).await?.is_null())
+            }
+        }
+    }
+
+    // Puts the browser window in full screen, if something (the meeting site, a dialog) took it out
+    pub async fn ensure_fullscreen(&self) -> BrowserResult<()> {
+        match self {
+            Browser::Cdp(page) => page.ensure_fullscreen().await,
+            Browser::WebDriver(driver) => Ok(driver.fullscreen_window().await?),
+            Browser::Webview(page) => page.ensure_fullscreen(),
+        }
+    }
+
+    pub async fn quit(&self) {
+        match self {
+            Browser::Cdp(page) => page.close(),
+            Browser::WebDriver(driver) => {
+                let _ = driver.clone().quit().await;
+            }
+            Browser::Webview(page) => page.close(),
+        }
+    }
+}
+
+async fn click_xpath_in_frames(driver: &WebDriver, xpath: &str) -> BrowserResult<bool> {
+    driver.enter_default_frame().await?;
+    let frame_count = driver.find_all(By::Tag("iframe")).await?.len();
+
+    // The top document first, then each iframe
+    for frame in std::iter::once(None).chain((0..frame_count).map(Some)) {
+        if let Some(i) = frame {
+            driver.enter_default_frame().await?;
+            if driver.enter_frame(i as u16).await.is_err() {
+                debug!(target: "zoom", "Failed to switch to iframe {}", i);
+                continue;
+            }
+        }
+
+        for element in driver.find_all(By::XPath(xpath.to_string())).await.unwrap_or_default() {
+            if !element.is_displayed().await.unwrap_or(false) {
+                continue;
+            }
+            let _ = element.scroll_into_view().await;
+            if let Err(e) = element.click().await {
+                // Something may be covering it, a script click still goes through
+                debug!(target: "zoom", "Standard click failed ({}), trying JavaScript click...", e);
+                driver.execute("arguments[0].click();", vec![element.to_json()?]).await?;
+            }
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Opens the browser for the configured backend, falling back to Chromium if the meeting window can't be used
+pub async fn launch(config: &BrowserConfig, meetings: &[MeetingConfig], app: &tauri::AppHandle, deadlines: &JoinDeadlines) -> Result<Browser, MeetingError> {
+    if config.backend == BrowserBackend::Webview {
+        info!(target: "zoom", "Opening meeting window...");
+        match WebviewPage::open(app, meetings) {
+            Ok(page) => return Ok(Browser::Webview(page)),
+            Err(e) => warn!(target: "zoom", "Could not use the meeting window ({}), falling back to Chromium", e),
+        }
+    }
+    launch_chromium(config, deadlines).await
+}
+
+// Starts a fresh Chromium with remote debugging and connects to it, directly over the
+// DevTools protocol or through ChromeDriver depending on the configured backend
+async fn launch_chromium(config: &BrowserConfig, deadlines: &JoinDeadlines) -> Result<Browser, MeetingError> {
+    // Kill any existing Chrome/Chromium processes
+    info!(target: "process", "Cleaning up existing Chrome processes...");
+    kill_matching("chromium").await;
+    
+    // The old Chromium has to be gone before a new one can use the profile
+    if !poll_until(PROCESS_EXIT_TIMEOUT, || async { !process_running("chromium").await }).await {
+        warn!(target: "process", "Old Chromium still running after {:?}, starting anyway", PROCESS_EXIT_TIMEOUT);
+    }
+
+    let debugging_port = ports::choose_port(config.debugging_port)
+        .map_err(|e| MeetingError::new(MeetingStep::LaunchChromium, e))?;
+
+    // Start Chromium directly with remote debugging
+    info!(target: "process", "Starting Chromium with remote debugging on port {}...", debugging_port);
+    let chromium = Command::new("/usr/bin/chromium-browser")
+        .env("DISPLAY", ":0")
+        .env("PULSE_RUNTIME_PATH", "/run/user/1000/pulse")
+        .env("PULSE_SERVER", "unix:/run/user/1000/pulse/native")
+        .arg(format!("--remote-debugging-port={}", debugging_port))
+        .arg("--user-data-dir=/home/rctv/.rctv-chrome-profile")
+        .arg("--autoplay-policy=no-user-gesture-required")
+        .arg("--disable-logging")
+        .arg("--log-level=3")
+        .arg("--disable-dev-shm-usage")
+        .arg("--disable-extensions")
+        .arg("--disable-gpu")
+        .arg("--hide-crash-restore-bubble")
+        .arg("--disable-session-crashed-bubble")
+        .arg("--allow-running-insecure-content")
+        .arg("--disable-features=VizDisplayCompositor")
+        .arg("--disable-user-media-security")
+        .arg("--allow-file-access-from-files")
+        .arg("--use-fake-ui-for-media-stream")
+        .arg("--allow-file-access")
+        .arg("--disable-features=MediaRouter")
+        .arg("--enable-features=VaapiVideoDecoder")
+        .stdout(Stdio::null()) // Hide Chromium output
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| MeetingError::new(MeetingStep::LaunchChromium, format!("could not start Chromium: {}", e)))?;
+    metrics::PROCESS_STARTS.with_label_values(&["chromium"]).inc();
+
+    // Wait for Chromium to start and check if debugging port is available
+    info!(target: "process", "Waiting for Chromium debugging port...");
+    let version_url = format!("http://localhost:{}/json/version", debugging_port);
+    let version_url = &version_url;
+    if !poll_until(config.startup_timeout(), || async move { reqwest::get(version_url).await.is_ok() }).await {
+        error!(target: "process", "Failed to connect to Chromium debugging port after {:?}", config.startup_timeout());
+        return Err(MeetingError::new(MeetingStep::LaunchChromium, format!("debugging port not available after {:?}", config.startup_timeout())));
+    }
+    // Whatever answers has to be the Chromium we just started, not a leftover or another program
+    if !ports::listener_belongs_to(debugging_port, chromium.id()) {
+        return Err(MeetingError::new(MeetingStep::LaunchChromium, format!("port {} is answered by another process", debugging_port)));
+    }
+    info!(target: "process", "Chromium debugging port is ready!");
+
+    if config.backend != BrowserBackend::WebDriver {
+        match run_step(MeetingStep::ConnectDevTools, deadlines.next_step(), CdpPage::connect(debugging_port)).await {
+            Ok(page) => return Ok(Browser::Cdp(page)),
+            Err(e) => warn!(target: "zoom", "Could not use the DevTools protocol ({}), falling back to ChromeDriver", e),
+        }
+    }
+    start_chromedriver(config, debugging_port, deadlines).await.map(Browser::WebDriver)
+}
+
+async fn start_chromedriver(config: &BrowserConfig, debugging_port: u16, deadlines: &JoinDeadlines) -> Result<WebDriver, MeetingError> {
+    let chromedriver_port = ports::choose_port(config.chromedriver_port)
+        .map_err(|e| MeetingError::new(MeetingStep::StartChromeDriver, e))?;
+
+    // Start ChromeDriver to bridge to existing Chromium
+    info!(target: "process", "Starting ChromeDriver on port {}...", chromedriver_port);
+    let chromedriver = Command::new("chromedriver")
+        .arg(format!("--port={}", chromedriver_port))
+        .arg("--whitelisted-ips=")
+        .arg("--silent")
+        .stdout(Stdio::null()) // Hide ChromeDriver output
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| MeetingError::new(MeetingStep::StartChromeDriver, format!("could not start ChromeDriver: {}", e)))?;
+    metrics::PROCESS_STARTS.with_label_values(&["chromedriver"]).inc();
+
+    // Wait for ChromeDriver to say it's ready
+    let status_url = format!("http://localhost:{}/status", chromedriver_port);
+    let status_url = &status_url;
+    let ready = poll_until(config.startup_timeout(), || async move {
+        match reqwest::get(status_url).await {
+            Ok(response) => response.json::<Value>().await.map(|status| status["value"]["ready"] == true).unwrap_or(false),
+            Err(_) => false,
+        }
+    }).await;
+    if !ready {
+        return Err(MeetingError::new(MeetingStep::StartChromeDriver, format!("not ready after {:?}", config.startup_timeout())));
+    }
+    if !ports::listener_belongs_to(chromedriver_port, chromedriver.id()) {
+        return Err(MeetingError::new(MeetingStep::StartChromeDriver, format!("ChromeDriver is not the one listening on port {}", chromedriver_port)));
+    }
+    run_step(MeetingStep::StartChromeDriver, deadlines.next_step(), connect_chromedriver(chromedriver_port, debugging_port)).await
+}
+
+async fn connect_chromedriver(chromedriver_port: u16, debugging_port: u16) -> BrowserResult<WebDriver> {
+    // Connect to ChromeDriver and tell it to use existing Chromium
+    let mut caps = DesiredCapabilities::chrome();
+    caps.add_experimental_option("debuggerAddress", format!("localhost:{}", debugging_port))?;
+    
+    info!(target: "process", "Connecting ChromeDriver to existing Chromium...");
+    Ok(WebDriver::new(format!("http://localhost:{}", chromedriver_port), caps).await?)
+}
+
+pub async fn print_page_info(browser: &Browser) -> BrowserResult<()> {
+    let current_url = browser.current_url().await?;
+    let page_title = browser.title().await?;
+    let page_source = browser.source().await?;
+    
+    debug!(target: "zoom", "Current URL: {}", current_url);
+    debug!(target: "zoom", "Page title: {}", page_title);
+    debug!(target: "zoom", "Page source length: {} characters", page_source.len());
+    // Not a byte slice, which could end in the middle of a character
+    debug!(target: "zoom", "First 500 chars of page: {}", page_source.chars().take(500).collect::<String>());
+    Ok(())
+}
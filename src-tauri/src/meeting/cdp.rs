@@ -0,0 +1,236 @@
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::info;
+
+use super::browser::{BrowserError, BrowserResult};
+
+// A command that gets no answer within this is treated as failed (the page may be hung)
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long a page may take to load after navigating to it
+const NAVIGATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+
+// A page in Chromium, driven over the DevTools protocol: one WebSocket to the browser
+// (from `/json/version`), with the page attached as a flattened session on it.
+pub struct CdpPage {
+    outgoing: mpsc::UnboundedSender<Message>,
+    pending: Pending,
+    events: broadcast::Sender<Value>, // Everything that isn't an answer to a command
+    next_id: AtomicU64,
+    session_id: String,
+}
+
+impl CdpPage {
+    pub async fn connect(debugging_port: u16) -> BrowserResult<CdpPage> {
+        let version: Value = reqwest::get(format!("http://localhost:{}/json/version", debugging_port))
+            .await?
+            .json()
+            .await?;
+        let ws_url = version["webSocketDebuggerUrl"]
+            .as_str()
+            .ok_or_else(|| BrowserError::new("no webSocketDebuggerUrl in /json/version"))?;
+
+        info!(target: "zoom", "Connecting to Chromium DevTools at {}", ws_url);
+        let (socket, _) = tokio_tungstenite::connect_async(ws_url)
+            .await
+            .map_err(|e| BrowserError::new(format!("could not connect to {}: {}", ws_url, e)))?;
+        let (mut sink, mut stream) = socket.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                let closing = matches!(message, Message::Close(_));
+                if sink.send(message).await.is_err() || closing {
+                    break;
+                }
+            }
+        });
+
+        // Answers are matched to commands by id, events go to whoever subscribed to them. When
+        // the socket closes the pending senders are dropped, which fails whoever is waiting on
+        // them, and so is the events sender.
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let pending_clone = pending.clone();
+        let (events, _) = broadcast::channel(256);
+        let events_clone = events.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = stream.next().await {
+                let Message::Text(text) = message else { continue };
+                let Ok(response) = serde_json::from_str::<Value>(&text) else { continue };
+                let Some(id) = response["id"].as_u64() else {
+                    // Nobody listening is fine
+                    let _ = events_clone.send(response);
+                    continue;
+                };
+                if let Some(sender) = pending_clone.lock().unwrap().remove(&id) {
+                    let result = match response.get("error") {
+                        Some(error) => Err(error["message"].as_str().unwrap_or("unknown error").to_string()),
+                        None => Ok(response["result"].clone()),
+                    };
+                    let _ = sender.send(result);
+                }
+            }
+            info!(target: "zoom", "Chromium DevTools connection closed");
+            pending_clone.lock().unwrap().clear();
+        });
+
+        let mut page = CdpPage { outgoing, pending, events, next_id: AtomicU64::new(1), session_id: String::new() };
+
+        // Attach to the tab Chromium opened on startup, or open one if there is none
+        let targets = page.send(None, "Target.getTargets", json!({})).await?;
+        let target_id = targets["targetInfos"]
+            .as_array()
+            .and_then(|targets| targets.iter().find(|target| target["type"] == "page"))
+            .and_then(|target| target["targetId"].as_str())
+            .map(|id| id.to_string());
+        let target_id = match target_id {
+            Some(id) => id,
+            None => {
+                let created = page.send(None, "Target.createTarget", json!({ "url": "about:blank" })).await?;
+                created["targetId"].as_str().unwrap_or_default().to_string()
+            }
+        };
+
+        let attached = page.send(None, "Target.attachToTarget", json!({ "targetId": target_id, "flatten": true })).await?;
+        page.session_id = attached["sessionId"]
+            .as_str()
+            .ok_or_else(|| BrowserError::new("attachToTarget returned no sessionId"))?
+            .to_string();
+        // For the load events navigate waits for
+        page.call("Page.enable", json!({})).await?;
+        info!(target: "zoom", "Attached to Chromium page {}", target_id);
+        Ok(page)
+    }
+
+    async fn send(&self, session_id: Option<&str>, method: &str, params: Value) -> BrowserResult<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut command = json!({ "id": id, "method": method, "params": params });
+        if let Some(session_id) = session_id {
+            command["sessionId"] = json!(session_id);
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        if self.outgoing.send(Message::Text(command.to_string().into())).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(BrowserError::new("DevTools connection is closed"));
+        }
+
+        match tokio::time::timeout(COMMAND_TIMEOUT, receiver).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(message))) => Err(BrowserError::new(format!("{} failed: {}", method, message))),
+            Ok(Err(_)) => Err(BrowserError::new("DevTools connection is closed")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(BrowserError::new(format!("{} timed out", method)))
+            }
+        }
+    }
+
+    // Sends a command to the page
+    pub async fn call(&self, method: &str, params: Value) -> BrowserResult<Value> {
+        self.send(Some(&self.session_id), method, params).await
+    }
+
+    // Like WebDriver's goto, doesn't return before the page has loaded. That's the `load` event
+    // after the frame has moved to the new document, so a previous page that was still loading
+    // doesn't count.
+    pub async fn navigate(&self, url: &str) -> BrowserResult<()> {
+        let mut events = self.events.subscribe();
+        let result = self.call("Page.navigate", json!({ "url": url })).await?;
+        if let Some(error) = result["errorText"].as_str() {
+            return Err(BrowserError::new(format!("could not open {}: {}", url, error)));
+        }
+        // Only the fragment changed: same document, nothing to load
+        let Some(loader_id) = result["loaderId"].as_str() else { return Ok(()) };
+
+        let loaded = async {
+            let mut navigated = false;
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return Err(BrowserError::new("DevTools connection is closed")),
+                };
+                if event["sessionId"] != self.session_id.as_str() {
+                    continue;
+                }
+                match event["method"].as_str() {
+                    Some("Page.frameNavigated") if event["params"]["frame"]["loaderId"] == loader_id => navigated = true,
+                    Some("Page.loadEventFired") if navigated => return Ok(()),
+                    _ => {}
+                }
+            }
+        };
+        tokio::time::timeout(NAVIGATION_TIMEOUT, loaded)
+            .await
+            .map_err(|_| BrowserError::new(format!("{} did not load within {} seconds", url, NAVIGATION_TIMEOUT.as_secs())))?
+    }
+
+    // Runs `script` as the body of a function, with `args` as its `arguments`, the same
+    // way WebDriver's execute does
+    pub async fn execute(&self, script: &str, args: Vec<Value>) -> BrowserResult<Value> {
+        let expression = format!("(function() {{\n{}\n}}).apply(null, {})", script, Value::Array(args));
+        let result = self.call("Runtime.evaluate", json!({
+            "expression": expression,
+            "returnByValue": true,
+            "awaitPromise": true,
+        })).await?;
+
+        if let Some(exception) = result.get("exceptionDetails") {
+            let description = exception["exception"]["description"]
+                .as_str()
+                .or_else(|| exception["text"].as_str())
+                .unwrap_or("unknown exception");
+            return Err(BrowserError::new(format!("JavaScript error: {}", description)));
+        }
+        Ok(result["result"]["value"].clone())
+    }
+
+    pub async fn screenshot_png(&self) -> BrowserResult<Vec<u8>> {
+        let result = self.call("Page.captureScreenshot", json!({ "format": "png" })).await?;
+        base64::engine::general_purpose::STANDARD
+            .decode(result["data"].as_str().unwrap_or_default())
+            .map_err(|e| BrowserError::new(format!("invalid screenshot data: {}", e)))
+    }
+
+    // A real (trusted) left click at page coordinates, some sign-in pages ignore script clicks
+    pub async fn click_at(&self, x: f64, y: f64) -> BrowserResult<()> {
+        for (event, button) in [("mouseMoved", "none"), ("mousePressed", "left"), ("mouseReleased", "left")] {
+            self.call("Input.dispatchMouseEvent", json!({
+                "type": event,
+                "x": x,
+                "y": y,
+                "button": button,
+                "clickCount": 1,
+            })).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn ensure_fullscreen(&self) -> BrowserResult<()> {
+        let target = self.call("Browser.getWindowForTarget", json!({})).await?;
+        let window_id = target["windowId"].clone();
+        if target["bounds"]["windowState"] == "fullscreen" {
+            return Ok(());
+        }
+        self.call("Browser.setWindowBounds", json!({
+            "windowId": window_id,
+            "bounds": { "windowState": "fullscreen" },
+        })).await?;
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        let _ = self.outgoing.send(Message::Close(None));
+    }
+}
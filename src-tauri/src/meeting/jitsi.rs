@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
+use url::Url;
+use tracing::{debug, info, warn};
+
+use super::{run_step, Browser, BrowserError, BrowserResult, JoinDeadlines, MeetingError, MeetingInfo, MeetingProvider, MeetingStatus, MeetingStep};
+use crate::config::MeetingConfig;
+
+// Works with meet.jit.si and self-hosted Jitsi Meet. Rather than clicking through the UI,
+// it talks to the `APP` object the Jitsi web app exposes on the page.
+pub struct JitsiProvider;
+
+#[async_trait]
+impl MeetingProvider for JitsiProvider {
+    async fn launch(&self, browser: &Browser, meeting: &MeetingConfig) -> BrowserResult<()> {
+        // Jitsi reads config overrides from the URL fragment: skip the pre-join screen and set our name
+        let mut url = Url::parse(&meeting.url)
+            .map_err(|e| BrowserError::new(format!("Invalid meeting URL {}: {}", meeting.url, e)))?;
+        url.set_fragment(Some(&format!(
+            "config.prejoinConfig.enabled=false&config.prejoinPageEnabled=false&userInfo.displayName={}",
+            serde_json::json!(meeting.display_name),
+        )));
+        browser.goto(url.as_str()).await
+    }
+
+    async fn join(&self, browser: &Browser, meeting: &MeetingConfig, deadlines: &JoinDeadlines) -> Result<(), MeetingError> {
+        info!(target: "zoom", "Waiting for Jitsi to load...");
+        run_step(MeetingStep::OpenMeeting, deadlines.next_step(), wait_for_app(browser)).await?;
+
+        info!(target: "zoom", "Waiting to be in the Jitsi meeting...");
+        run_step(MeetingStep::JoinButton, deadlines.next_step(), wait_until_joined(browser, meeting)).await?;
+        info!(target: "zoom", "Successfully joined Jitsi meeting!");
+        Ok(())
+    }
+
+    async fn info(&self, browser: &Browser) -> BrowserResult<MeetingInfo> {
+        let probe = probe_meeting(browser).await?;
+        Ok(MeetingInfo {
+            status: classify_meeting(&probe),
+            url: probe.url,
+            participants: probe.participants,
+            audio_muted: probe.audio_muted,
+            video_on: probe.video_muted.map(|muted| !muted),
+            speaker_view: probe.tile_view.map(|tile_view| !tile_view),
+        })
+    }
+
+    async fn leave(&self, browser: &Browser) -> BrowserResult<()> {
+        browser.execute("APP.conference.hangup(false);", Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn set_muted(&self, browser: &Browser, muted: bool) -> BrowserResult<()> {
+        browser.execute("APP.conference.muteAudio(arguments[0]);", vec![serde_json::json!(muted)]).await?;
+        Ok(())
+    }
+
+    async fn set_camera(&self, browser: &Browser, on: bool) -> BrowserResult<()> {
+        browser.execute("APP.conference.muteVideo(!arguments[0]);", vec![serde_json::json!(on)]).await?;
+        Ok(())
+    }
+
+    // Same Redux actions the toolbar buttons dispatch
+    async fn set_speaker_view(&self, browser: &Browser) -> BrowserResult<()> {
+        browser.execute("APP.store.dispatch({ type: 'SET_TILE_VIEW', enabled: false });", Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn hide_self_view(&self, browser: &Browser) -> BrowserResult<()> {
+        browser.execute("APP.store.dispatch({ type: 'SETTINGS_UPDATED', settings: { disableSelfView: true } });", Vec::new()).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MeetingProbe {
+    url: String,
+    loaded: bool,
+    joined: bool,
+    prejoin: bool,
+    knocking: bool,
+    error: Option<String>,
+    participants: Option<u32>,
+    audio_muted: Option<bool>,
+    video_muted: Option<bool>,
+    tile_view: Option<bool>,
+}
+
+const MEETING_PROBE_SCRIPT: &str = r#"
+    const app = typeof APP !== 'undefined' ? APP : null;
+    const state = app && app.store ? app.store.getState() : null;
+    const conference = (state && state['features/base/conference']) || {};
+    const lobby = (state && state['features/lobby']) || {};
+    const layout = (state && state['features/video-layout']) || {};
+    const joined = !!(app && app.conference && app.conference.isJoined && app.conference.isJoined());
+    return {
+        url: location.href,
+        loaded: state !== null,
+        joined: joined,
+        prejoin: document.querySelector('[data-testid="prejoin.joinMeeting"]') !== null,
+        knocking: !!lobby.knocking,
+        error: conference.error ? String(conference.error.name || conference.error) : null,
+        participants: joined ? app.conference.membersCount : null,
+        audio_muted: joined ? app.conference.isLocalAudioMuted() : null,
+        video_muted: joined ? app.conference.isLocalVideoMuted() : null,
+        tile_view: joined && typeof layout.tileViewEnabled === 'boolean' ? layout.tileViewEnabled : null,
+    };
+"#;
+
+fn classify_meeting(probe: &MeetingProbe) -> MeetingStatus {
+    // After hanging up (or being kicked) Jitsi shows its close page or keeps a conference error around
+    if probe.url.contains("/static/close") || probe.error.is_some() {
+        MeetingStatus::Ended
+    } else if probe.knocking {
+        MeetingStatus::InWaitingRoom
+    } else if probe.joined {
+        MeetingStatus::Joined
+    } else if probe.loaded && !probe.prejoin {
+        MeetingStatus::Waiting
+    } else {
+        MeetingStatus::NotJoined
+    }
+}
+
+async fn probe_meeting(browser: &Browser) -> BrowserResult<MeetingProbe> {
+    let result = browser.execute(MEETING_PROBE_SCRIPT, Vec::new()).await?;
+    serde_json::from_value(result)
+        .map_err(|e| BrowserError::new(format!("Unexpected meeting probe result: {}", e)))
+}
+
+async fn wait_for_app(browser: &Browser) -> BrowserResult<()> {
+    loop {
+        match probe_meeting(browser).await {
+            Ok(probe) if probe.loaded => return Ok(()),
+            Ok(_) => debug!(target: "zoom", "Jitsi not loaded yet, retrying in 2 seconds..."),
+            Err(e) => warn!(target: "zoom", "Could not check if Jitsi is loaded: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+async fn wait_until_joined(browser: &Browser, meeting: &MeetingConfig) -> BrowserResult<()> {
+    loop {
+        let probe = probe_meeting(browser).await?;
+        match classify_meeting(&probe) {
+            MeetingStatus::Joined => return Ok(()),
+            MeetingStatus::Ended => {
+                return Err(BrowserError::new(format!("Jitsi conference failed: {}", probe.error.unwrap_or_default())));
+            }
+            status => debug!(target: "zoom", "Not in Jitsi meeting yet, status: {:?}", status),
+        }
+
+        // Some deployments don't allow skipping the pre-join screen, fill it in ourselves
+        if probe.prejoin {
+            info!(target: "zoom", "Jitsi pre-join screen showing, entering name and joining...");
+            let _ = browser.execute(PREJOIN_SCRIPT, vec![serde_json::json!(meeting.display_name)]).await;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+const PREJOIN_SCRIPT: &str = r#"
+    const input = document.querySelector('#premeeting-name-input');
+    if (input && !input.value) {
+        const setter = Object.getOwnPropertyDescriptor(HTMLInputElement.prototype, 'value').set;
+        setter.call(input, arguments[0]);
+        input.dispatchEvent(new Event('input', { bubbles: true }));
+    }
+    const join = document.querySelector('[data-testid="prejoin.joinMeeting"]');
+    if (join) join.click();
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meeting::assert_classifies;
+    use serde_json::json;
+
+    #[test]
+    fn classifies_meeting_pages() {
+        let meeting = "https://meet.jit.si/RecurseRCTV";
+        assert_classifies(classify_meeting, &[
+            (json!({"url": meeting, "loaded": true, "joined": true, "participants": 3}), MeetingStatus::Joined),
+            (json!({"url": meeting, "loaded": true, "prejoin": true}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "loaded": true}), MeetingStatus::Waiting),
+            (json!({"url": meeting, "loaded": true, "knocking": true}), MeetingStatus::InWaitingRoom),
+            // Kicked, or the conference went away, even if Jitsi still says joined
+            (json!({"url": meeting, "loaded": true, "joined": true, "error": "conference.destroyed"}), MeetingStatus::Ended),
+            (json!({"url": "https://meet.jit.si/static/close.html"}), MeetingStatus::Ended),
+            (json!({"url": meeting, "loaded": false}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "error": null, "participants": null}), MeetingStatus::NotJoined),
+        ]);
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use super::{run_step, wait_for_camera, Browser, BrowserError, BrowserResult, JoinDeadlines, MeetingError, MeetingInfo, MeetingProvider, MeetingStatus, MeetingStep};
+use crate::config::MeetingConfig;
+
+// Google Meet, using the Google account the Chromium profile is already signed into
+// (the same one Zoom signs in with). Meet's markup is minified, so this relies on the
+// few attributes that have stayed stable (`data-is-muted`, `jsname`s) plus English text
+// as a fallback.
+pub struct MeetProvider;
+
+#[async_trait]
+impl MeetingProvider for MeetProvider {
+    async fn join(&self, browser: &Browser, _meeting: &MeetingConfig, deadlines: &JoinDeadlines) -> Result<(), MeetingError> {
+        wait_for_camera(browser, deadlines.camera_timeout).await;
+
+        info!(target: "zoom", "Looking for Meet Join now / Ask to join button...");
+        run_step(MeetingStep::JoinButton, deadlines.next_step(), click_join_button(browser)).await?;
+
+        info!(target: "zoom", "Waiting to be let into the Meet call...");
+        run_step(MeetingStep::JoinButton, deadlines.next_step(), wait_until_joined(browser)).await?;
+        info!(target: "zoom", "Successfully joined Meet call!");
+        Ok(())
+    }
+
+    async fn info(&self, browser: &Browser) -> BrowserResult<MeetingInfo> {
+        let probe = probe_meeting(browser).await?;
+        Ok(MeetingInfo {
+            status: classify_meeting(&probe),
+            url: probe.url,
+            participants: probe.participants,
+            audio_muted: probe.audio_muted,
+            video_on: probe.video_muted.map(|muted| !muted),
+            speaker_view: None,
+        })
+    }
+
+    async fn leave(&self, browser: &Browser) -> BrowserResult<()> {
+        browser.execute(LEAVE_SCRIPT, Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn set_muted(&self, browser: &Browser, muted: bool) -> BrowserResult<()> {
+        let result = browser.execute(SET_MUTED_SCRIPT, vec![serde_json::json!(muted)]).await?;
+        if result.is_null() {
+            return Err(BrowserError::new("Meet microphone button not found"));
+        }
+        Ok(())
+    }
+
+    async fn set_camera(&self, browser: &Browser, on: bool) -> BrowserResult<()> {
+        let result = browser.execute(SET_CAMERA_SCRIPT, vec![serde_json::json!(on)]).await?;
+        if result.is_null() {
+            return Err(BrowserError::new("Meet camera button not found"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MeetingProbe {
+    url: String,
+    join_button: bool,
+    leave_button: bool,
+    asking: bool,
+    ended: bool,
+    participants: Option<u32>,
+    audio_muted: Option<bool>,
+    video_muted: Option<bool>,
+}
+
+// The first two `data-is-muted` toggles are the microphone and the camera, on the
+// pre-join screen as well as in the call
+const MEETING_PROBE_SCRIPT: &str = r#"
+    const text = document.body ? document.body.innerText : '';
+    const toggles = document.querySelectorAll('[data-is-muted]');
+    const muted = (element) => element ? element.getAttribute('data-is-muted') === 'true' : null;
+    const count = document.querySelector('[data-participant-count]');
+    const participants = count ? parseInt(count.textContent.replace(/[^0-9]/g, ''), 10) : NaN;
+    return {
+        url: location.href,
+        join_button: document.querySelector('[jsname="Qx7uuf"]') !== null || /Join now|Ask to join/.test(text),
+        leave_button: document.querySelector('[jsname="CQylAd"], [aria-label="Leave call"]') !== null,
+        asking: /Asking to be let in|Asking to join/.test(text),
+        ended: /You left the meeting|You've been removed|The call has ended/.test(text),
+        participants: Number.isNaN(participants) ? null : participants,
+        audio_muted: muted(toggles[0]),
+        video_muted: muted(toggles[1]),
+    };
+"#;
+
+const JOIN_SCRIPT: &str = r#"
+    let button = document.querySelector('[jsname="Qx7uuf"]');
+    if (!button) {
+        button = Array.from(document.querySelectorAll('button'))
+            .find((element) => /Join now|Ask to join/.test(element.innerText));
+    }
+    if (button) button.click();
+    return !!button;
+"#;
+
+const LEAVE_SCRIPT: &str = r#"
+    const button = document.querySelector('[jsname="CQylAd"], [aria-label="Leave call"]');
+    if (button) button.click();
+"#;
+
+const SET_MUTED_SCRIPT: &str = r#"
+    const button = document.querySelectorAll('[data-is-muted]')[0];
+    if (!button) return null;
+    const muted = button.getAttribute('data-is-muted') === 'true';
+    if (muted !== arguments[0]) button.click();
+    return muted;
+"#;
+
+const SET_CAMERA_SCRIPT: &str = r#"
+    const button = document.querySelectorAll('[data-is-muted]')[1];
+    if (!button) return null;
+    const on = button.getAttribute('data-is-muted') !== 'true';
+    if (on !== arguments[0]) button.click();
+    return on;
+"#;
+
+fn classify_meeting(probe: &MeetingProbe) -> MeetingStatus {
+    if probe.ended {
+        MeetingStatus::Ended
+    } else if probe.asking {
+        MeetingStatus::InWaitingRoom
+    } else if probe.leave_button && !probe.join_button {
+        MeetingStatus::Joined
+    } else if probe.join_button || !probe.url.contains("meet.google.com/") {
+        MeetingStatus::NotJoined
+    } else {
+        MeetingStatus::Waiting
+    }
+}
+
+async fn probe_meeting(browser: &Browser) -> BrowserResult<MeetingProbe> {
+    let result = browser.execute(MEETING_PROBE_SCRIPT, Vec::new()).await?;
+    serde_json::from_value(result)
+        .map_err(|e| BrowserError::new(format!("Unexpected meeting probe result: {}", e)))
+}
+
+async fn click_join_button(browser: &Browser) -> BrowserResult<()> {
+    loop {
+        let clicked = browser.execute(JOIN_SCRIPT, Vec::new()).await?;
+        if clicked.as_bool() == Some(true) {
+            info!(target: "zoom", "Clicked Meet join button");
+            return Ok(());
+        }
+        debug!(target: "zoom", "Meet join button not found, retrying in 2 seconds...");
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+// Someone in the call may have to let us in, so waiting room time counts towards this step
+async fn wait_until_joined(browser: &Browser) -> BrowserResult<()> {
+    loop {
+        match classify_meeting(&probe_meeting(browser).await?) {
+            MeetingStatus::Joined => return Ok(()),
+            MeetingStatus::Ended => return Err(BrowserError::new("Meet call ended before we got in")),
+            status => debug!(target: "zoom", "Not in Meet call yet, status: {:?}", status),
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meeting::assert_classifies;
+    use serde_json::json;
+
+    #[test]
+    fn classifies_meeting_pages() {
+        let meeting = "https://meet.google.com/abc-defg-hij";
+        assert_classifies(classify_meeting, &[
+            (json!({"url": meeting, "leave_button": true, "participants": 5}), MeetingStatus::Joined),
+            (json!({"url": meeting, "join_button": true}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "leave_button": true, "join_button": true}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "asking": true}), MeetingStatus::InWaitingRoom),
+            (json!({"url": meeting}), MeetingStatus::Waiting),
+            (json!({"url": meeting, "ended": true, "leave_button": true}), MeetingStatus::Ended),
+            // Signed out, sent to the account chooser
+            (json!({"url": "https://accounts.google.com/signin"}), MeetingStatus::NotJoined),
+            (json!({}), MeetingStatus::NotJoined),
+        ]);
+    }
+}
@@ -0,0 +1,277 @@
+use async_trait::async_trait;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{Config, MeetingConfig, MeetingPolicy, MeetingSettings, ProviderKind};
+use crate::diagnostics;
+
+mod browser;
+mod cdp;
+mod jitsi;
+mod meet;
+mod policy;
+mod ports;
+mod watcher;
+pub mod webview;
+mod zoom;
+
+pub use browser::{kill_chrome_processes, process_ids, Browser, BrowserError, BrowserResult};
+pub use policy::apply_policy;
+pub use watcher::{watch_meeting, MeetingClock};
+pub use webview::{close_meeting_window, WebviewScripts};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeetingStatus {
+    NotJoined,          // Sign-in or pre-join page, or not on a meeting page at all
+    Waiting,            // Connecting, or waiting for the host to start the meeting
+    InWaitingRoom,      // Host has put us in the waiting room / lobby
+    Joined,             // In the meeting
+    Ended,              // Meeting ended, or we were removed from it
+}
+
+// What a provider can tell about the meeting page
+#[derive(Debug, Clone)]
+pub struct MeetingInfo {
+    pub status: MeetingStatus,
+    pub url: String,
+    pub participants: Option<u32>,
+    pub audio_muted: Option<bool>,
+    pub video_on: Option<bool>,
+    pub speaker_view: Option<bool>, // Speaker (true) or gallery / tile view (false)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeetingStep {
+    LaunchChromium,     // Starting Chromium and waiting for its debugging port
+    ConnectDevTools,    // Attaching to Chromium's page over the DevTools protocol
+    StartChromeDriver,  // Starting ChromeDriver and attaching it to Chromium
+    OpenMeeting,        // Loading the meeting page
+    SignInLink,         // Zoom: finding and clicking the "sign in" link
+    GoogleSignIn,       // Zoom: finding and clicking "Sign in with Google"
+    SelectAccount,      // Zoom: picking the "Recurse RCTV" Google account
+    JoinButton,         // Clicking Join until we're in the meeting
+}
+
+impl std::fmt::Display for MeetingStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let description = match self {
+            MeetingStep::LaunchChromium => "launching Chromium",
+            MeetingStep::ConnectDevTools => "connecting to Chromium DevTools",
+            MeetingStep::StartChromeDriver => "starting ChromeDriver",
+            MeetingStep::OpenMeeting => "opening the meeting page",
+            MeetingStep::SignInLink => "clicking the sign in link",
+            MeetingStep::GoogleSignIn => "clicking Sign in with Google",
+            MeetingStep::SelectAccount => "selecting the Recurse RCTV account",
+            MeetingStep::JoinButton => "clicking the Join button",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+#[derive(Debug)]
+pub struct MeetingError {
+    pub step: MeetingStep,
+    pub reason: String,
+}
+
+impl MeetingError {
+    pub fn new(step: MeetingStep, reason: impl Into<String>) -> Self {
+        MeetingError { step, reason: reason.into() }
+    }
+}
+
+impl std::fmt::Display for MeetingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "failed while {}: {}", self.step, self.reason)
+    }
+}
+
+impl std::error::Error for MeetingError {}
+
+// Each step gets `step_timeout`, but never past the deadline for the whole automation.
+// The camera and join confirmation waits are shorter and don't fail the step when they run out.
+pub struct JoinDeadlines {
+    overall: tokio::time::Instant,
+    step_timeout: Duration,
+    pub camera_timeout: Duration,
+    pub join_confirm_timeout: Duration,
+}
+
+impl JoinDeadlines {
+    fn new(settings: &MeetingSettings) -> Self {
+        JoinDeadlines {
+            overall: tokio::time::Instant::now() + settings.overall_timeout(),
+            step_timeout: settings.step_timeout(),
+            camera_timeout: settings.camera_timeout(),
+            join_confirm_timeout: settings.join_confirm_timeout(),
+        }
+    }
+
+    pub fn next_step(&self) -> tokio::time::Instant {
+        std::cmp::min(tokio::time::Instant::now() + self.step_timeout, self.overall)
+    }
+}
+
+// Run one automation step, turning browser errors and timeouts into a MeetingError for that step
+pub async fn run_step<T>(
+    step: MeetingStep,
+    deadline: tokio::time::Instant,
+    future: impl std::future::Future<Output = BrowserResult<T>>,
+) -> Result<T, MeetingError> {
+    let started = tokio::time::Instant::now();
+    match tokio::time::timeout_at(deadline, future).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(MeetingError::new(step, e.to_string())),
+        Err(_) => Err(MeetingError::new(step, format!("timed out after {} seconds", started.elapsed().as_secs()))),
+    }
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Checks `ready` every POLL_INTERVAL until it's true or `timeout` has passed. Returns whether it got ready.
+pub async fn poll_until<F, Fut>(timeout: Duration, mut ready: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if ready().await {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// 'live' once a <video> on the page (or a same-origin iframe) shows a live camera track,
+// 'no-camera' if the machine has none, so there's nothing to wait for
+const CAMERA_STATE_SCRIPT: &str = r#"
+    const docs = [document];
+    for (const frame of document.querySelectorAll('iframe')) {
+        try { if (frame.contentDocument) docs.push(frame.contentDocument); } catch (e) {}
+    }
+    for (const doc of docs) {
+        for (const video of doc.querySelectorAll('video')) {
+            const stream = video.srcObject;
+            if (stream && stream.getVideoTracks && stream.getVideoTracks().some((track) => track.readyState === 'live')) {
+                return 'live';
+            }
+        }
+    }
+    if (!navigator.mediaDevices || !navigator.mediaDevices.enumerateDevices) return 'no-camera';
+    return navigator.mediaDevices.enumerateDevices()
+        .then((devices) => devices.some((device) => device.kind === 'videoinput') ? 'waiting' : 'no-camera');
+"#;
+
+// Waits for the camera preview to be running, so we don't join with a black tile. Joining
+// without it is better than not joining, so this only logs when it gives up.
+pub async fn wait_for_camera(browser: &Browser, timeout: Duration) {
+    info!(target: "zoom", "Waiting up to {:?} for the camera to start...", timeout);
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let state = match browser.execute(CAMERA_STATE_SCRIPT, Vec::new()).await {
+            Ok(value) => value.as_str().unwrap_or_default().to_string(),
+            Err(e) => e.to_string(),
+        };
+        match state.as_str() {
+            "live" => {
+                info!(target: "zoom", "Camera is running");
+                return;
+            }
+            "no-camera" => {
+                info!(target: "zoom", "No camera found, not waiting for it");
+                return;
+            }
+            _ if tokio::time::Instant::now() >= deadline => {
+                warn!(target: "zoom", "Camera not running after {:?} ({}), continuing anyway", timeout, state);
+                return;
+            }
+            _ => tokio::time::sleep(POLL_INTERVAL).await,
+        }
+    }
+}
+
+// One implementation per video call service. They all drive the same Chromium, through `Browser`.
+#[async_trait]
+pub trait MeetingProvider: Send + Sync {
+    // Opens the meeting page
+    async fn launch(&self, browser: &Browser, meeting: &MeetingConfig) -> BrowserResult<()> {
+        browser.goto(&meeting.url).await
+    }
+
+    // Gets from the meeting page into the meeting: signing in, pre-join screen, Join button...
+    async fn join(&self, browser: &Browser, meeting: &MeetingConfig, deadlines: &JoinDeadlines) -> Result<(), MeetingError>;
+
+    async fn info(&self, browser: &Browser) -> BrowserResult<MeetingInfo>;
+
+    async fn leave(&self, browser: &Browser) -> BrowserResult<()>;
+
+    async fn set_muted(&self, browser: &Browser, muted: bool) -> BrowserResult<()>;
+
+    async fn set_camera(&self, browser: &Browser, on: bool) -> BrowserResult<()>;
+
+    async fn set_speaker_view(&self, _browser: &Browser) -> BrowserResult<()> {
+        Err(BrowserError::new("not supported for this provider"))
+    }
+
+    async fn hide_self_view(&self, _browser: &Browser) -> BrowserResult<()> {
+        Err(BrowserError::new("not supported for this provider"))
+    }
+}
+
+fn provider_for(kind: ProviderKind) -> Box<dyn MeetingProvider> {
+    match kind {
+        ProviderKind::Zoom => Box::new(zoom::ZoomProvider),
+        ProviderKind::Jitsi => Box::new(jitsi::JitsiProvider),
+        ProviderKind::Meet => Box::new(meet::MeetProvider),
+    }
+}
+
+// The meeting we're in: shared by the watcher and anything else that acts on the meeting
+pub struct MeetingSession {
+    pub browser: Browser,
+    pub provider: Box<dyn MeetingProvider>,
+    pub meeting: MeetingConfig,
+    pub policy: std::sync::Mutex<MeetingPolicy>, // Starts as the meeting's policy, button presses can change it
+}
+
+// Launches Chromium and gets into `meeting`, giving up once a deadline is hit
+pub async fn start_meeting(config: &Config, meeting: &MeetingConfig, app: &tauri::AppHandle) -> Result<MeetingSession, MeetingError> {
+    let deadlines = JoinDeadlines::new(&config.meeting);
+    let browser = browser::launch(&config.browser, &config.meetings, app, &deadlines).await?;
+    let provider = provider_for(meeting.provider);
+
+    info!(target: "zoom", "Joining meeting {} ({:?})", meeting.name, meeting.provider);
+    let joined = async {
+        run_step(MeetingStep::OpenMeeting, deadlines.next_step(), provider.launch(&browser, meeting)).await?;
+        provider.join(&browser, meeting, &deadlines).await
+    }.await;
+
+    // Keep a screenshot and the page source around when a step fails, to see what the provider changed
+    if let Err(e) = joined {
+        diagnostics::save_snapshot(&browser, &config.diagnostics, &format!("{:?}", e.step), &e.to_string()).await;
+        return Err(e);
+    }
+
+    let session = MeetingSession { browser, provider, meeting: meeting.clone(), policy: std::sync::Mutex::new(meeting.policy.clone()) };
+    match session.provider.info(&session.browser).await {
+        Ok(info) => apply_policy(&session, &info, true).await,
+        Err(e) => warn!(target: "zoom", "Could not check meeting state to apply policy: {}", e),
+    }
+
+    info!(target: "zoom", "Automation complete!");
+    Ok(session)
+}
+
+// Checks a provider's classify_meeting against probes written as JSON, the way its
+// MEETING_PROBE_SCRIPT returns them
+#[cfg(test)]
+fn assert_classifies<P: serde::de::DeserializeOwned>(classify: fn(&P) -> MeetingStatus, cases: &[(serde_json::Value, MeetingStatus)]) {
+    for (probe, expected) in cases {
+        let parsed: P = serde_json::from_value(probe.clone()).unwrap();
+        assert_eq!(classify(&parsed), *expected, "{}", probe);
+    }
+}
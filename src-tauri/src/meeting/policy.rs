@@ -0,0 +1,57 @@
+use tracing::{info, warn};
+use super::{MeetingInfo, MeetingSession};
+
+// Brings the meeting in line with the session's policy. Microphone, camera and layout are
+// compared with what the provider reports and only changed when they differ. Self view can't
+// be read back, so it's only set right after joining (`just_joined`).
+pub async fn apply_policy(session: &MeetingSession, info: &MeetingInfo, just_joined: bool) {
+    let policy = session.policy.lock().unwrap().clone();
+    let browser = &session.browser;
+    let provider = &session.provider;
+
+    if let Some(muted) = policy.mic_muted {
+        if info.audio_muted.is_some_and(|current| current != muted) {
+            info!(target: "zoom", "Microphone is {}, policy says {}", on_off(!info.audio_muted.unwrap_or_default()), on_off(!muted));
+            if let Err(e) = provider.set_muted(browser, muted).await {
+                warn!(target: "zoom", "Could not set microphone: {}", e);
+            }
+        }
+    }
+
+    if let Some(camera_on) = policy.camera_on {
+        if info.video_on.is_some_and(|current| current != camera_on) {
+            info!(target: "zoom", "Camera is {}, policy says {}", on_off(info.video_on.unwrap_or_default()), on_off(camera_on));
+            if let Err(e) = provider.set_camera(browser, camera_on).await {
+                warn!(target: "zoom", "Could not set camera: {}", e);
+            }
+        }
+    }
+
+    let wrong_layout = match info.speaker_view {
+        Some(speaker_view) => !speaker_view,
+        None => just_joined,
+    };
+    if policy.speaker_view && wrong_layout {
+        info!(target: "zoom", "Switching to speaker view");
+        if let Err(e) = provider.set_speaker_view(browser).await {
+            warn!(target: "zoom", "Could not switch to speaker view: {}", e);
+        }
+    }
+
+    if policy.hide_self_view && just_joined {
+        info!(target: "zoom", "Hiding self view");
+        if let Err(e) = provider.hide_self_view(browser).await {
+            warn!(target: "zoom", "Could not hide self view: {}", e);
+        }
+    }
+
+    if policy.full_screen {
+        if let Err(e) = browser.ensure_fullscreen().await {
+            warn!(target: "zoom", "Could not make the browser full screen: {}", e);
+        }
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
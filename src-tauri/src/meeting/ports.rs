@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::net::TcpListener;
+
+// Lets the kernel pick a port nothing is listening on. Someone else could grab it before
+// we use it, which `listener_belongs_to` catches afterwards.
+pub fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+pub fn port_in_use(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_err()
+}
+
+// `configured` is the port from the config file, 0 meaning pick one
+pub fn choose_port(configured: u16) -> Result<u16, String> {
+    if configured == 0 {
+        return free_port().map_err(|e| format!("could not find a free port: {}", e));
+    }
+    if port_in_use(configured) {
+        return Err(format!("port {} is already in use by another process", configured));
+    }
+    Ok(configured)
+}
+
+// Whether the socket listening on `port` is held by `pid` or one of its children
+// (chromium-browser is a wrapper script on some distributions). Linux only, from /proc.
+pub fn listener_belongs_to(port: u16, pid: u32) -> bool {
+    let inodes = listening_inodes(port);
+    if inodes.is_empty() {
+        return false;
+    }
+
+    let Ok(processes) = std::fs::read_dir("/proc") else { return false };
+    processes
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&candidate| is_same_or_child(candidate, pid))
+        .any(|candidate| holds_socket(candidate, &inodes))
+}
+
+// Socket inodes listening on `port`, from /proc/net/tcp and tcp6
+fn listening_inodes(port: u16) -> HashSet<String> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| std::fs::read_to_string(table).ok())
+        .flat_map(|contents| inodes_listening_on(&contents, port))
+        .collect()
+}
+
+// The same from the contents of one of those tables
+fn inodes_listening_on(table: &str, port: u16) -> Vec<String> {
+    const LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || fields[3] != LISTEN {
+                return None;
+            }
+            let local_port = fields[1].rsplit(':').next().and_then(|hex| u16::from_str_radix(hex, 16).ok());
+            (local_port == Some(port)).then(|| fields[9].to_string())
+        })
+        .collect()
+}
+
+fn holds_socket(pid: u32, inodes: &HashSet<String>) -> bool {
+    let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else { return false };
+    fds.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+        .filter_map(|target| socket_inode(&target.to_string_lossy()).map(str::to_string))
+        .any(|inode| inodes.contains(&inode))
+}
+
+// What a /proc/<pid>/fd link to a socket points at, "socket:[12345]"
+fn socket_inode(target: &str) -> Option<&str> {
+    target.strip_prefix("socket:[")?.strip_suffix(']')
+}
+
+fn is_same_or_child(mut pid: u32, ancestor: u32) -> bool {
+    while pid > 1 {
+        if pid == ancestor {
+            return true;
+        }
+        pid = match parent_pid(pid) {
+            Some(parent) => parent,
+            None => return false,
+        };
+    }
+    false
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    parent_pid_in(&std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+// From the contents of /proc/<pid>/stat
+fn parent_pid_in(stat: &str) -> Option<u32> {
+    // "pid (comm) state ppid ...", and comm can contain spaces and parentheses
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    after_comm.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP_HEADER: &str =
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode";
+
+    #[test]
+    fn finds_inodes_listening_on_a_port() {
+        let tcp = [
+            TCP_HEADER,
+            // 127.0.0.1:9222 listening
+            "   0: 0100007F:2406 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0",
+            // 0.0.0.0:9222, established rather than listening
+            "   1: 00000000:2406 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 41300 1 0000000000000000 20 4 30 10 -1",
+            // 127.0.0.1:9223 listening
+            "   2: 0100007F:2407 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41235 1 0000000000000000 100 0 0 10 0",
+            // cut short
+            "   3: 0100007F:2406 00000000:0000 0A",
+        ]
+        .join("\n");
+        let tcp6 = [
+            TCP_HEADER,
+            // [::1]:65535 listening
+            "   0: 00000000000000000000000001000000:FFFF 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 52000 1 0000000000000000 100 0 0 10 0",
+        ]
+        .join("\n");
+
+        let cases: &[(&str, u16, &[&str])] = &[
+            (&tcp, 9222, &["41234"]),
+            (&tcp, 9223, &["41235"]),
+            (&tcp, 80, &[]),
+            (&tcp6, 65535, &["52000"]),
+            (&tcp6, 9222, &[]),
+            (TCP_HEADER, 9222, &[]),
+            ("", 9222, &[]),
+        ];
+        for (table, port, expected) in cases {
+            assert_eq!(inodes_listening_on(table, *port), *expected, "port {} in {:?}", port, table);
+        }
+    }
+
+    #[test]
+    fn reads_socket_inodes_from_fd_links() {
+        let cases = [
+            ("socket:[41234]", Some("41234")),
+            ("socket:[]", Some("")),
+            ("pipe:[41234]", None),
+            ("/dev/null", None),
+            ("socket:[41234", None),
+        ];
+        for (target, expected) in cases {
+            assert_eq!(socket_inode(target), expected, "{}", target);
+        }
+    }
+
+    #[test]
+    fn reads_parent_pids() {
+        let cases = [
+            ("1234 (chromium) S 1200 1234 1234 0 -1 4194560", Some(1200)),
+            ("1234 (Web Content) S 1200 1234 1234 0 -1", Some(1200)),
+            ("1234 (a) b (c)) R 77 1234 1234 0 -1", Some(77)),
+            ("1 (systemd) S 0 1 1 0 -1", Some(0)),
+            ("1234 (chromium) S", None),
+            ("1234 (chromium) S notapid 1234", None),
+            ("1234 chromium S 1200", None),
+            ("", None),
+        ];
+        for (stat, expected) in cases {
+            assert_eq!(parent_pid_in(stat), expected, "{}", stat);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{apply_policy, kill_chrome_processes, Browser, BrowserResult, MeetingSession, MeetingStatus};
+use crate::config::MeetingSettings;
+use crate::metrics;
+use crate::{return_to_kiosk_mode, AutomationState};
+
+// When the current meeting gets hung up. Shared between the meeting watcher and the
+// HID controller, so a button press during the warning can push the deadline back.
+#[derive(Debug, Default)]
+pub struct MeetingClock {
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl MeetingClock {
+    fn start(&mut self, config: &MeetingSettings) {
+        self.deadline = Some(tokio::time::Instant::now() + config.max_meeting_length());
+    }
+
+    fn stop(&mut self) {
+        self.deadline = None;
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()))
+    }
+
+    fn in_warning_period(&self, config: &MeetingSettings) -> bool {
+        self.remaining().is_some_and(|remaining| remaining <= config.warning_period())
+    }
+
+    // Only extends while the warning is showing, returns whether it did
+    pub fn extend(&mut self, config: &MeetingSettings) -> bool {
+        if config.extension_minutes == 0 || !self.in_warning_period(config) {
+            return false;
+        }
+        if let Some(deadline) = self.deadline.as_mut() {
+            *deadline += config.extension();
+        }
+        true
+    }
+}
+
+// Banner at the top of the meeting page, plus a short beep the first time it shows up
+const SHOW_END_WARNING_SCRIPT: &str = r#"
+    const doc = window.top.document;
+    let banner = doc.getElementById('rctv-end-warning');
+    if (!banner) {
+        banner = doc.createElement('div');
+        banner.id = 'rctv-end-warning';
+        Object.assign(banner.style, {
+            position: 'fixed', top: '0', left: '0', right: '0', zIndex: '2147483647',
+            padding: '16px', textAlign: 'center', font: 'bold 32px sans-serif',
+            color: 'white', background: 'rgba(190, 30, 45, 0.9)', pointerEvents: 'none',
+        });
+        doc.body.appendChild(banner);
+    }
+    banner.textContent = arguments[0];
+    if (arguments[1]) {
+        try {
+            const audio = new AudioContext();
+            const beep = audio.createOscillator();
+            beep.frequency.value = 880;
+            beep.connect(audio.destination);
+            beep.start();
+            beep.stop(audio.currentTime + 0.4);
+        } catch (e) {}
+    }
+"#;
+
+const HIDE_END_WARNING_SCRIPT: &str = r#"
+    const banner = window.top.document.getElementById('rctv-end-warning');
+    if (banner) banner.remove();
+"#;
+
+async fn show_end_warning(browser: &Browser, remaining: Duration, config: &MeetingSettings, beep: bool) -> BrowserResult<()> {
+    let minutes = remaining.as_secs().div_ceil(60);
+    let mut message = format!("This meeting will end in {} minute{}.", minutes, if minutes == 1 { "" } else { "s" });
+    if config.extension_minutes > 0 {
+        message.push_str(&format!(" Press the button to add {} minutes.", config.extension_minutes));
+    }
+    browser.execute(SHOW_END_WARNING_SCRIPT, vec![serde_json::json!(message), serde_json::json!(beep)]).await?;
+    Ok(())
+}
+
+async fn hide_end_warning(browser: &Browser) -> BrowserResult<()> {
+    browser.execute(HIDE_END_WARNING_SCRIPT, Vec::new()).await?;
+    Ok(())
+}
+
+// How many failed probes in a row we take as the WebDriver session being gone
+const MAX_PROBE_FAILURES: u32 = 3;
+
+// Watches the meeting while we're in it, and goes back to kiosk mode once the meeting
+// ends, we get removed from it, the connection to the browser goes away, or the
+// maximum meeting length is reached.
+pub async fn watch_meeting(session: Arc<MeetingSession>, settings: MeetingSettings, clock: Arc<std::sync::Mutex<MeetingClock>>, token: String, app_handle: Arc<tauri::AppHandle>, state: Arc<std::sync::Mutex<AutomationState>>) {
+    let browser = &session.browser;
+    clock.lock().unwrap().start(&settings);
+    let joined = std::time::Instant::now();
+    info!(target: "zoom", "Meeting will be hung up after {} minutes", settings.max_meeting_minutes);
+    
+    let mut last_status = None;
+    let mut probe_failures = 0;
+    let mut warning_shown = false;
+    let reason = loop {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        
+        {
+            let current_state = state.lock().unwrap();
+            if *current_state != AutomationState::ZoomComplete {
+                info!(target: "zoom", "Stopping meeting watcher, current state: {:?}", *current_state);
+                clock.lock().unwrap().stop();
+                metrics::MEETING_DURATION.observe(joined.elapsed().as_secs_f64());
+                return;
+            }
+        }
+        
+        let (remaining, in_warning_period) = {
+            let clock = clock.lock().unwrap();
+            (clock.remaining(), clock.in_warning_period(&settings))
+        };
+        if remaining == Some(Duration::ZERO) {
+            break "reached the maximum meeting length";
+        }
+        if in_warning_period {
+            if !warning_shown {
+                info!(target: "zoom", "Meeting ends in {:?}, showing warning", remaining.unwrap_or_default());
+            }
+            if let Err(e) = show_end_warning(browser, remaining.unwrap_or_default(), &settings, !warning_shown).await {
+                warn!(target: "zoom", "Could not show end of meeting warning: {}", e);
+            }
+            warning_shown = true;
+        } else if warning_shown {
+            info!(target: "zoom", "Meeting was extended, hiding warning");
+            let _ = hide_end_warning(browser).await;
+            warning_shown = false;
+        }
+        
+        match session.provider.info(browser).await {
+            Ok(info) => {
+                probe_failures = 0;
+                let status = info.status;
+                if last_status != Some(status) {
+                    info!(target: "zoom", "Meeting status: {:?} (participants: {:?}, muted: {:?}, video: {:?}) at {}",
+                          status, info.participants, info.audio_muted, info.video_on, info.url);
+                }
+                match status {
+                    MeetingStatus::Ended => break "meeting ended",
+                    // Seeing the pre-join page twice in a row after being in the meeting means we were removed
+                    MeetingStatus::NotJoined if last_status == Some(MeetingStatus::NotJoined) => break "no longer in the meeting",
+                    // Put back anything the provider changed on us (e.g. camera turned back on)
+                    MeetingStatus::Joined => apply_policy(&session, &info, false).await,
+                    _ => {}
+                }
+                last_status = Some(status);
+            }
+            Err(e) => {
+                probe_failures += 1;
+                warn!(target: "zoom", "Could not check meeting status ({} of {}): {}", probe_failures, MAX_PROBE_FAILURES, e);
+                if probe_failures >= MAX_PROBE_FAILURES {
+                    break "lost the connection to the browser";
+                }
+            }
+        }
+    };
+    
+    metrics::MEETING_DURATION.observe(joined.elapsed().as_secs_f64());
+
+    // The button may have been pressed in the meantime, only one of us gets to stop the meeting
+    {
+        let mut state_guard = state.lock().unwrap();
+        if *state_guard != AutomationState::ZoomComplete {
+            return;
+        }
+        *state_guard = AutomationState::Stopping;
+    }
+    
+    clock.lock().unwrap().stop();
+    info!(target: "zoom", "Leaving {} ({}), returning to kiosk mode", session.meeting.name, reason);
+    // Hang up properly when we can, so others don't see the TV drop out. Not worth waiting on a hung browser.
+    let _ = tokio::time::timeout(Duration::from_secs(10), session.provider.leave(browser)).await;
+    browser.quit().await;
+    kill_chrome_processes().await;
+    return_to_kiosk_mode(token, app_handle, state);
+}
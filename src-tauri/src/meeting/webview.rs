@@ -0,0 +1,211 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::webview::PageLoadEvent;
+use tauri::{Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tokio::sync::{broadcast, oneshot};
+use url::Url;
+
+use super::browser::{BrowserError, BrowserResult};
+use crate::config::MeetingConfig;
+
+pub const MEETING_WINDOW: &str = "meeting";
+
+// Same as the main window, some meeting sites refuse browsers they don't know
+const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long a page may take to load after navigating to it
+const NAVIGATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Scripts run with `eval`, which can't return anything, so they send their result back
+// through the `meeting_script_result` command. Managed as Tauri state.
+#[derive(Default)]
+pub struct WebviewScripts {
+    pending: Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>,
+    next_id: AtomicU64,
+    capability_added: AtomicBool,
+}
+
+#[tauri::command]
+pub fn meeting_script_result(scripts: tauri::State<'_, WebviewScripts>, id: u64, ok: bool, value: Value) {
+    if let Some(sender) = scripts.pending.lock().unwrap().remove(&id) {
+        let _ = sender.send(if ok { Ok(value) } else { Err(value.as_str().unwrap_or("unknown error").to_string()) });
+    }
+}
+
+// Wraps `script` like WebDriver's execute (a function body, `args` as its `arguments`), and
+// reports what it returns, or throws, back to us
+const SCRIPT_WRAPPER: &str = r#"
+(async () => {
+    const send = (ok, value) => window.__TAURI_INTERNALS__.invoke('meeting_script_result', { id: __ID__, ok, value });
+    try {
+        const value = await (async function() {
+__SCRIPT__
+        }).apply(null, __ARGS__);
+        send(true, value === undefined ? null : value);
+    } catch (e) {
+        send(false, String((e && e.stack) || e));
+    }
+})();
+"#;
+
+// The meeting in a second Tauri window, instead of an external Chromium
+pub struct WebviewPage {
+    window: WebviewWindow,
+    app: tauri::AppHandle,
+    page_loads: broadcast::Sender<PageLoadEvent>, // Loads starting and finishing in the window
+}
+
+impl WebviewPage {
+    pub fn open(app: &tauri::AppHandle, meetings: &[MeetingConfig]) -> BrowserResult<WebviewPage> {
+        allow_ipc_from(app, meetings)?;
+
+        let blank = WebviewUrl::External("about:blank".parse().expect("valid URL"));
+        let (page_loads, _) = broadcast::channel(16);
+        let page_loads_sender = page_loads.clone();
+        let window = WebviewWindowBuilder::new(app, MEETING_WINDOW, blank)
+            .title("RCTV Meeting")
+            .fullscreen(true)
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .user_agent(USER_AGENT)
+            .on_page_load(move |_, payload| {
+                let _ = page_loads_sender.send(payload.event());
+            })
+            .build()
+            .map_err(|e| BrowserError::new(format!("could not open meeting window: {}", e)))?;
+        if let Err(e) = grant_media_permissions(&window) {
+            let _ = window.close();
+            return Err(e);
+        }
+        let _ = window.set_cursor_visible(false);
+
+        Ok(WebviewPage { window, app: app.clone(), page_loads })
+    }
+
+    // Doesn't return before the new page has loaded (and can answer scripts): a load that
+    // started after navigating has finished, so a previous page still loading doesn't count
+    pub async fn navigate(&self, url: &str) -> BrowserResult<()> {
+        let parsed = Url::parse(url).map_err(|e| BrowserError::new(format!("invalid URL {}: {}", url, e)))?;
+        let mut page_loads = self.page_loads.subscribe();
+        self.window.navigate(parsed).map_err(|e| BrowserError::new(e.to_string()))?;
+
+        let loaded = async {
+            let mut started = false;
+            loop {
+                match page_loads.recv().await {
+                    Ok(PageLoadEvent::Started) => started = true,
+                    Ok(PageLoadEvent::Finished) if started => return Ok(()),
+                    Ok(PageLoadEvent::Finished) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return Err(BrowserError::new("meeting window closed")),
+                }
+            }
+        };
+        tokio::time::timeout(NAVIGATION_TIMEOUT, loaded)
+            .await
+            .map_err(|_| BrowserError::new(format!("{} did not load within {} seconds", url, NAVIGATION_TIMEOUT.as_secs())))?
+    }
+
+    pub async fn execute(&self, script: &str, args: Vec<Value>) -> BrowserResult<Value> {
+        let scripts = self.app.state::<WebviewScripts>();
+        let id = scripts.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        scripts.pending.lock().unwrap().insert(id, sender);
+
+        let wrapped = SCRIPT_WRAPPER
+            .replace("__ID__", &id.to_string())
+            .replace("__ARGS__", &Value::Array(args).to_string())
+            .replace("__SCRIPT__", script);
+        if let Err(e) = self.window.eval(&wrapped) {
+            scripts.pending.lock().unwrap().remove(&id);
+            return Err(BrowserError::new(format!("could not run script: {}", e)));
+        }
+
+        match tokio::time::timeout(SCRIPT_TIMEOUT, receiver).await {
+            Ok(Ok(Ok(value))) => Ok(value),
+            Ok(Ok(Err(message))) => Err(BrowserError::new(format!("JavaScript error: {}", message))),
+            Ok(Err(_)) => Err(BrowserError::new("script result was dropped")),
+            Err(_) => {
+                // The page navigated away before answering, or can't reach the IPC
+                scripts.pending.lock().unwrap().remove(&id);
+                Err(BrowserError::new("script did not answer"))
+            }
+        }
+    }
+
+    pub fn current_url(&self) -> BrowserResult<String> {
+        Ok(self.window.url().map_err(|e| BrowserError::new(e.to_string()))?.to_string())
+    }
+
+    pub fn ensure_fullscreen(&self) -> BrowserResult<()> {
+        if self.window.is_fullscreen().unwrap_or(false) {
+            return Ok(());
+        }
+        self.window.set_fullscreen(true).map_err(|e| BrowserError::new(e.to_string()))
+    }
+
+    pub fn close(&self) {
+        let _ = self.window.close();
+    }
+}
+
+// Remote pages only get the IPC for origins listed in a capability. Meeting URLs come from
+// the config file (self-hosted Jitsi...), so the capability is built here rather than in
+// capabilities/.
+fn allow_ipc_from(app: &tauri::AppHandle, meetings: &[MeetingConfig]) -> BrowserResult<()> {
+    if app.state::<WebviewScripts>().capability_added.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mut capability = tauri::ipc::CapabilityBuilder::new("meeting-window")
+        .window(MEETING_WINDOW)
+        .permission("core:default");
+    for meeting in meetings {
+        if let Ok(url) = Url::parse(&meeting.url) {
+            capability = capability.remote(format!("{}/*", url.origin().ascii_serialization()));
+        }
+    }
+    // Zoom sends us through its sign-in pages, and Zoom and Meet through Google's
+    for origin in ["https://*.zoom.us/*", "https://accounts.google.com/*"] {
+        capability = capability.remote(origin.to_string());
+    }
+    app.add_capability(capability)
+        .map_err(|e| BrowserError::new(format!("could not allow meeting window IPC: {}", e)))
+}
+
+// Camera and microphone requests are answered yes without asking, like Chromium's
+// --use-fake-ui-for-media-stream
+#[cfg(target_os = "linux")]
+fn grant_media_permissions(window: &WebviewWindow) -> BrowserResult<()> {
+    window
+        .with_webview(|webview| {
+            use webkit2gtk::{PermissionRequestExt, SettingsExt, WebViewExt};
+            let view = webview.inner();
+            if let Some(settings) = WebViewExt::settings(&view) {
+                settings.set_enable_media_stream(true);
+                settings.set_enable_webrtc(true);
+                settings.set_media_playback_requires_user_gesture(false);
+            }
+            view.connect_permission_request(|_, request| {
+                request.allow();
+                true
+            });
+        })
+        .map_err(|e| BrowserError::new(format!("could not set up meeting window: {}", e)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn grant_media_permissions(_window: &WebviewWindow) -> BrowserResult<()> {
+    Err(BrowserError::new("granting media permissions is only supported on Linux"))
+}
+
+// Closes the meeting window if there is one
+pub fn close_meeting_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(MEETING_WINDOW) {
+        let _ = window.close();
+    }
+}
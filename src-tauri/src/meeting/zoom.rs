@@ -0,0 +1,406 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::browser::print_page_info;
+use super::{poll_until, run_step, wait_for_camera, Browser, BrowserError, BrowserResult, JoinDeadlines, MeetingError, MeetingInfo, MeetingProvider, MeetingStatus, MeetingStep};
+use crate::config::MeetingConfig;
+
+pub struct ZoomProvider;
+
+#[async_trait]
+impl MeetingProvider for ZoomProvider {
+    async fn join(&self, browser: &Browser, _meeting: &MeetingConfig, deadlines: &JoinDeadlines) -> Result<(), MeetingError> {
+        join_meeting(browser, deadlines).await
+    }
+
+    async fn info(&self, browser: &Browser) -> BrowserResult<MeetingInfo> {
+        let probe = probe_meeting(browser).await?;
+        Ok(MeetingInfo {
+            status: classify_meeting(&probe),
+            url: probe.url,
+            participants: probe.participants,
+            audio_muted: probe.audio_muted,
+            video_on: probe.video_on,
+            speaker_view: probe.speaker_view,
+        })
+    }
+
+    async fn leave(&self, browser: &Browser) -> BrowserResult<()> {
+        let script = format!("{}{}", FRAMES_PRELUDE, CLICK_SCRIPT);
+        browser.execute(&script, vec![serde_json::json!(".footer__leave-btn")]).await?;
+        // Zoom asks for confirmation before leaving
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        browser.execute(&script, vec![serde_json::json!(".leave-meeting-options__btn")]).await?;
+        Ok(())
+    }
+
+    async fn set_muted(&self, browser: &Browser, muted: bool) -> BrowserResult<()> {
+        let script = format!("{}{}{}", FRAMES_PRELUDE, BUTTON_STATE_PRELUDE, SET_MUTED_SCRIPT);
+        let result = browser.execute(&script, vec![serde_json::json!(muted)]).await?;
+        if result.is_null() {
+            return Err(BrowserError::new("Zoom microphone button not found, or its state can't be read"));
+        }
+        Ok(())
+    }
+
+    async fn set_camera(&self, browser: &Browser, on: bool) -> BrowserResult<()> {
+        let script = format!("{}{}{}", FRAMES_PRELUDE, BUTTON_STATE_PRELUDE, SET_CAMERA_SCRIPT);
+        let result = browser.execute(&script, vec![serde_json::json!(on)]).await?;
+        if result.is_null() {
+            return Err(BrowserError::new("Zoom camera button not found, or its state can't be read"));
+        }
+        Ok(())
+    }
+
+    async fn set_speaker_view(&self, browser: &Browser) -> BrowserResult<()> {
+        choose_view_menu_item(browser, SPEAKER_VIEW_ITEM).await
+    }
+
+    async fn hide_self_view(&self, browser: &Browser) -> BrowserResult<()> {
+        choose_view_menu_item(browser, HIDE_SELF_VIEW_ITEM).await
+    }
+}
+
+// Entries of the View menu, top right of the meeting
+const SPEAKER_VIEW_ITEM: &str = "//a[contains(text(), 'Speaker View')] | //li[contains(text(), 'Speaker View')] | //*[@role='menuitem'][contains(., 'Speaker')]";
+const HIDE_SELF_VIEW_ITEM: &str = "//a[contains(text(), 'Hide Self View')] | //li[contains(text(), 'Hide Self View')] | //*[@role='menuitem'][contains(., 'Hide Self View')]";
+
+async fn choose_view_menu_item(browser: &Browser, item: &str) -> BrowserResult<()> {
+    let script = format!("{}{}", FRAMES_PRELUDE, CLICK_SCRIPT);
+    let opened = browser.execute(&script, vec![serde_json::json!(".full-screen-widget__button, [aria-label='View']")]).await?;
+    if opened != true {
+        return Err(BrowserError::new("Zoom View menu not found"));
+    }
+    // The menu opens with an animation
+    if !poll_until(Duration::from_secs(3), || async { browser.has_xpath(item).await.unwrap_or(false) }).await {
+        return Err(BrowserError::new("option not found in the Zoom View menu"));
+    }
+    if !browser.click_xpath(item).await? {
+        return Err(BrowserError::new("could not click the Zoom View menu option"));
+    }
+    Ok(())
+}
+
+// What the probe script sees on the page. Only element classes/ids and the URL are
+// used to decide the status, so it keeps working with a non-English Zoom UI. Microphone and
+// camera state are the exception, see BUTTON_STATE_PRELUDE: they're None if it can't tell.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MeetingProbe {
+    url: String,
+    toolbar: bool,
+    leave_button: bool,
+    join_button: bool,
+    connecting: bool,
+    waiting_for_host: bool,
+    waiting_room: bool,
+    ended: bool,
+    participants: Option<u32>,
+    audio_muted: Option<bool>,
+    video_on: Option<bool>,
+    speaker_view: Option<bool>,
+}
+
+// Runs in whatever frame the driver is in: it looks at the top document and every
+// same-origin iframe (the Zoom web client lives in one), without switching frames.
+const FRAMES_PRELUDE: &str = r#"
+    let top;
+    try { top = window.top.document; } catch (e) { top = document; }
+    const docs = [top];
+    for (const frame of top.querySelectorAll('iframe')) {
+        try { if (frame.contentDocument) docs.push(frame.contentDocument); } catch (e) {}
+    }
+    const find = (selector) => {
+        for (const doc of docs) {
+            const element = doc.querySelector(selector);
+            if (element) return element;
+        }
+        return null;
+    };
+"#;
+
+// Microphone and camera state, from their toolbar buttons. Nothing in Zoom's classes tells, so
+// this goes by the button's aria-label, which is only understood in English: with another
+// language (or once Zoom rewords it) the state is null, and nothing gets toggled the wrong way.
+const BUTTON_STATE_PRELUDE: &str = r#"
+    const ariaLabel = (button) => (button.getAttribute('aria-label') || '').trim().toLowerCase();
+    const micMuted = (button) => {
+        const label = ariaLabel(button);
+        if (/^unmute\b/.test(label)) return true;
+        if (/^mute\b/.test(label)) return false;
+        return null;
+    };
+    const cameraOn = (button) => {
+        const label = ariaLabel(button);
+        if (/^stop\b/.test(label)) return true;
+        if (/^start\b/.test(label)) return false;
+        return null;
+    };
+"#;
+
+const MEETING_PROBE_SCRIPT: &str = r#"
+    const has = (selector) => find(selector) !== null;
+
+    const counter = find('.footer-button__number-counter');
+    const participants = counter ? parseInt(counter.textContent.replace(/[^0-9]/g, ''), 10) : NaN;
+    const audio = find('.join-audio-container__btn');
+    const video = find('.send-video-container__btn');
+
+    return {
+        url: (() => { try { return window.top.location.href; } catch (e) { return location.href; } })(),
+        toolbar: has('#wc-footer, .footer__inner, #foot-bar'),
+        leave_button: has('.footer__leave-btn, .footer__leave-btn-container, .leave-meeting-options__btn'),
+        join_button: has('.preview-join-button, #joinBtn, .zm-btn--primary.joinWindowBtn'),
+        connecting: has('.loading-layer, .meeting-client-inner .loading-main, .ReactModal__Content .connecting'),
+        waiting_for_host: has('.waiting-for-host, .wc-waiting-for-host, #wc-content .waiting-room-container'),
+        waiting_room: has('.wr-default, .wr-content, .wr-tile, .waiting-room-root'),
+        ended: has('.meeting-ended, .zm-modal-body-title.meeting-ended, .postattendee, #postattendee'),
+        participants: Number.isNaN(participants) ? null : participants,
+        audio_muted: audio ? micMuted(audio) : null,
+        video_on: video ? cameraOn(video) : null,
+        speaker_view: has('.speaker-active-container, .speaker-view') ? true : (has('.gallery-video-container') ? false : null),
+    };
+"#;
+
+// Clicks the first element matching `arguments[0]`
+const CLICK_SCRIPT: &str = r#"
+    const element = find(arguments[0]);
+    if (element) element.click();
+    return element !== null;
+"#;
+
+// Clicks the microphone button if it isn't already in the `arguments[0]` (muted) state. Null,
+// without clicking, if that state can't be read.
+const SET_MUTED_SCRIPT: &str = r#"
+    const button = find('.join-audio-container__btn');
+    if (!button) return null;
+    const muted = micMuted(button);
+    if (muted === null) return null;
+    if (muted !== arguments[0]) button.click();
+    return muted;
+"#;
+
+// Clicks the camera button if it isn't already in the `arguments[0]` (on) state. Null, without
+// clicking, if that state can't be read.
+const SET_CAMERA_SCRIPT: &str = r#"
+    const button = find('.send-video-container__btn');
+    if (!button) return null;
+    const on = cameraOn(button);
+    if (on === null) return null;
+    if (on !== arguments[0]) button.click();
+    return on;
+"#;
+
+fn classify_meeting(probe: &MeetingProbe) -> MeetingStatus {
+    // Zoom sends you to a post-attendee / leave page once the meeting is over
+    let ended_url = probe.url.contains("/postattendee") || probe.url.contains("/wc/leave");
+    let meeting_url = probe.url.contains("/wc/") || probe.url.contains("/j/");
+
+    if probe.ended || ended_url {
+        MeetingStatus::Ended
+    } else if probe.waiting_room {
+        MeetingStatus::InWaitingRoom
+    } else if meeting_url && probe.toolbar && probe.leave_button && !probe.join_button {
+        MeetingStatus::Joined
+    } else if probe.waiting_for_host || probe.connecting {
+        MeetingStatus::Waiting
+    } else {
+        MeetingStatus::NotJoined
+    }
+}
+
+async fn probe_meeting(browser: &Browser) -> BrowserResult<MeetingProbe> {
+    let result = browser.execute(&format!("{}{}{}", FRAMES_PRELUDE, BUTTON_STATE_PRELUDE, MEETING_PROBE_SCRIPT), Vec::new()).await?;
+    serde_json::from_value(result)
+        .map_err(|e| BrowserError::new(format!("Unexpected meeting probe result: {}", e)))
+}
+
+async fn check_if_in_meeting(browser: &Browser) -> bool {
+    // Check the meeting toolbar/URL to confirm we're actually in the meeting
+    match probe_meeting(browser).await {
+        Ok(probe) => match classify_meeting(&probe) {
+            MeetingStatus::Joined => {
+                info!(target: "zoom", "Meeting toolbar found - confirmed in meeting! ({:?} participants)", probe.participants);
+                true
+            }
+            status => {
+                debug!(target: "zoom", "Not in meeting yet, status: {:?} (url: {})", status, probe.url);
+                false
+            }
+        },
+        Err(e) => {
+            warn!(target: "zoom", "Could not check meeting status: {}", e);
+            false
+        }
+    }
+}
+
+// Zoom's text is matched the same way everywhere: in the page or any iframe, visible elements only
+const SIGN_IN_LINK: &str = "//a[contains(text(), 'sign in')]";
+const GOOGLE_BUTTON: &str = "//a[@aria-label='Sign in with Google']";
+const RECURSE_ACCOUNT: &str = "//div[contains(text(), 'Recurse RCTV')]";
+const MIC_CAMERA_BUTTON: &str = "//button[contains(text(), 'Use microphone and camera')] | //*[contains(text(), 'Use microphone and camera')]";
+const JOIN_BUTTON: &str = "//button[contains(text(), 'Join')] | //input[@value='Join'] | //*[contains(text(), 'Join')]";
+
+// Keeps trying to click `xpath` until it shows up
+async fn wait_and_click(browser: &Browser, xpath: &str, what: &str) -> BrowserResult<()> {
+    loop {
+        match browser.click_xpath(xpath).await {
+            Ok(true) => {
+                info!(target: "zoom", "Clicked {}", what);
+                return Ok(());
+            }
+            Ok(false) => debug!(target: "zoom", "{} not found, retrying in 2 seconds...", what),
+            Err(e) => debug!(target: "zoom", "Could not click {} ({}), retrying in 2 seconds...", what, e),
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+async fn is_in_meeting(browser: &Browser) -> bool {
+    matches!(probe_meeting(browser).await, Ok(probe) if classify_meeting(&probe) == MeetingStatus::Joined)
+}
+
+// The page is usable once it's loaded and shows either the Join button (already signed in)
+// or the sign in link. Zoom redirects a few times on the way, so this keeps checking.
+async fn wait_for_zoom_page(browser: &Browser) -> BrowserResult<()> {
+    loop {
+        let loaded = matches!(browser.execute("return document.readyState;", Vec::new()).await, Ok(state) if state == "complete");
+        if loaded && (browser.has_xpath(JOIN_BUTTON).await.unwrap_or(false) || browser.has_xpath(SIGN_IN_LINK).await.unwrap_or(false)) {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn click_join_button(browser: &Browser, confirm_timeout: Duration) -> BrowserResult<()> {
+    let mut join_attempts = 0;
+    loop {
+        let clicked = match browser.click_xpath(JOIN_BUTTON).await {
+            Ok(clicked) => clicked,
+            Err(e) => {
+                warn!(target: "zoom", "Could not click Join button: {}", e);
+                false
+            }
+        };
+        if !clicked {
+            debug!(target: "zoom", "Join button not found anywhere, retrying in 2 seconds...");
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            continue;
+        }
+
+        join_attempts += 1;
+        info!(target: "zoom", "Clicked Join button (attempt {} of 10), waiting to see if it worked...", join_attempts);
+        if poll_until(confirm_timeout, || is_in_meeting(browser)).await || check_if_in_meeting(browser).await {
+            info!(target: "zoom", "Successfully joined meeting!");
+            return Ok(());
+        }
+        if join_attempts >= 10 {
+            warn!(target: "zoom", "Too many failed attempts, giving up on Join button");
+            return Err(BrowserError::new("Gave up on Join button after 10 attempts"));
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+async fn join_meeting(browser: &Browser, deadlines: &JoinDeadlines) -> Result<(), MeetingError> {
+    info!(target: "zoom", "Successfully opened Zoom meeting in Chromium");
+    
+    info!(target: "zoom", "Waiting for the Zoom page to load...");
+    run_step(MeetingStep::OpenMeeting, deadlines.next_step(), wait_for_zoom_page(browser)).await?;
+    
+    // Debug: Check what page we're on
+    run_step(MeetingStep::OpenMeeting, deadlines.next_step(), print_page_info(browser)).await?;
+    
+    // Check if we're already signed in and can see the Join button immediately
+    info!(target: "zoom", "Checking if already signed in (looking for Join button)...");
+    let skip_signin = browser.has_xpath(JOIN_BUTTON).await.unwrap_or(false);
+    
+    if skip_signin {
+        info!(target: "zoom", "Join button found immediately - already signed in, skipping sign-in process");
+        
+        wait_for_camera(browser, deadlines.camera_timeout).await;
+    } else {
+        info!(target: "zoom", "Join button not found, proceeding with sign-in process...");
+        
+        // Step 1: click "sign in" link (it's in the web client iframe)
+        info!(target: "zoom", "Looking for sign in link...");
+        run_step(MeetingStep::SignInLink, deadlines.next_step(), wait_and_click(browser, SIGN_IN_LINK, "sign in link")).await?;
+        
+        // Step 2: Wait for and click Google sign-in link
+        info!(target: "zoom", "Looking for Google sign-in link...");
+        run_step(MeetingStep::GoogleSignIn, deadlines.next_step(), wait_and_click(browser, GOOGLE_BUTTON, "Google sign-in button")).await?;
+        
+        // Step 3: Wait for and click "Recurse RCTV" account
+        info!(target: "zoom", "Looking for Recurse RCTV account...");
+        run_step(MeetingStep::SelectAccount, deadlines.next_step(), wait_and_click(browser, RECURSE_ACCOUNT, "Recurse RCTV account")).await?;
+        
+        // Step 4: Look for optional "Use microphone and camera" button (don't block if not found)
+        info!(target: "zoom", "Looking for optional Use microphone and camera button...");
+        let mut found_mic_camera = false;
+        for attempt in 1..=3 {
+            match browser.click_xpath(MIC_CAMERA_BUTTON).await {
+                Ok(true) => {
+                    info!(target: "zoom", "Clicked Use microphone and camera button");
+                    found_mic_camera = true;
+                    break;
+                }
+                Ok(false) => debug!(target: "zoom", "Use microphone and camera button not found (attempt {})", attempt),
+                Err(e) => warn!(target: "zoom", "Could not click microphone button: {}", e),
+            }
+            if attempt < 3 {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+        }
+        if !found_mic_camera {
+            info!(target: "zoom", "Use microphone and camera button not found - continuing without it");
+        }
+        
+        // Step 5: Wait for camera to initialize before looking for Join button
+        wait_for_camera(browser, deadlines.camera_timeout).await;
+    } // End of sign-in process
+    
+    // Step 6: Click "Join" until we're in the meeting
+    info!(target: "zoom", "Looking for Join button...");
+    run_step(MeetingStep::JoinButton, deadlines.next_step(), click_join_button(browser, deadlines.join_confirm_timeout)).await?;
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meeting::assert_classifies;
+    use serde_json::json;
+
+    #[test]
+    fn classifies_meeting_pages() {
+        let meeting = "https://us02web.zoom.us/wc/81234567890/join?pwd=abc";
+        assert_classifies(classify_meeting, &[
+            (json!({"url": meeting, "toolbar": true, "leave_button": true, "participants": 4}), MeetingStatus::Joined),
+            (json!({"url": "https://zoom.us/j/81234567890", "toolbar": true, "leave_button": true}), MeetingStatus::Joined),
+            // Still on the pre-join screen
+            (json!({"url": meeting, "toolbar": true, "leave_button": true, "join_button": true}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "join_button": true}), MeetingStatus::NotJoined),
+            (json!({"url": meeting, "connecting": true}), MeetingStatus::Waiting),
+            (json!({"url": meeting, "waiting_for_host": true}), MeetingStatus::Waiting),
+            (json!({"url": meeting, "waiting_room": true, "toolbar": true, "leave_button": true}), MeetingStatus::InWaitingRoom),
+            (json!({"url": meeting, "ended": true, "toolbar": true, "leave_button": true}), MeetingStatus::Ended),
+            (json!({"url": "https://us02web.zoom.us/postattendee?mn=81234567890"}), MeetingStatus::Ended),
+            (json!({"url": "https://us02web.zoom.us/wc/leave"}), MeetingStatus::Ended),
+            // A toolbar somewhere else isn't a meeting
+            (json!({"url": "https://zoom.us/signin", "toolbar": true, "leave_button": true}), MeetingStatus::NotJoined),
+            (json!({"url": "about:blank"}), MeetingStatus::NotJoined),
+            (json!({}), MeetingStatus::NotJoined),
+        ]);
+    }
+
+    #[test]
+    fn reads_probes_with_missing_and_null_fields() {
+        let probe: MeetingProbe = serde_json::from_value(json!({"url": "https://zoom.us/wc/1/join", "participants": null, "audio_muted": true})).unwrap();
+        assert_eq!(probe.participants, None);
+        assert_eq!(probe.audio_muted, Some(true));
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::LazyLock;
+
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+use tracing::warn;
+
+// Served as /metrics by the control API. Everything is registered on first use.
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(metric: M) -> M {
+    REGISTRY.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+fn counter(name: &str, help: &str) -> IntCounter {
+    register(IntCounter::new(name, help).unwrap())
+}
+
+fn counter_vec(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    register(IntCounterVec::new(Opts::new(name, help), labels).unwrap())
+}
+
+// Playlist fetches from the RCTV server, by result ("success" or "failure")
+pub static PLAYLIST_FETCHES: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_playlist_fetches_total", "Playlist fetches from the RCTV server", &["result"]));
+
+pub static PLAYLIST_FETCH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let opts = HistogramOpts::new("rctv_playlist_fetch_duration_seconds", "Time taken by playlist fetches")
+        .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]);
+    register(HistogramVec::new(opts, &["result"]).unwrap())
+});
+
+// By URL for the playlist's. Those shown with /show could be anything, they're all counted as "api".
+pub static SLIDES_SHOWN: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_slides_shown_total", "Kiosk URLs put on screen", &["url"]));
+
+pub static NAVIGATION_ERRORS: LazyLock<IntCounter> =
+    LazyLock::new(|| counter("rctv_navigation_errors_total", "Kiosk URLs that could not be parsed or navigated to"));
+
+pub static MEETING_JOINS_ATTEMPTED: LazyLock<IntCounter> =
+    LazyLock::new(|| counter("rctv_meeting_joins_attempted_total", "Meeting joins started"));
+
+pub static MEETING_JOINS_SUCCEEDED: LazyLock<IntCounter> =
+    LazyLock::new(|| counter("rctv_meeting_joins_succeeded_total", "Meetings joined"));
+
+// By the step that failed (MeetingStep), or "panic"
+pub static MEETING_JOINS_FAILED: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_meeting_joins_failed_total", "Meeting joins that failed, by step", &["step"]));
+
+pub static MEETING_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    let opts = HistogramOpts::new("rctv_meeting_duration_seconds", "Time spent in meetings, from joining to leaving")
+        .buckets(vec![60.0, 300.0, 900.0, 1800.0, 3600.0, 5400.0, 7200.0, 10800.0]);
+    register(Histogram::with_opts(opts).unwrap())
+});
+
+// Raw events from the buttons, by input ("hid", "evdev" or "gpio"), before telling presses apart
+pub static INPUT_EVENTS: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_input_events_total", "Events received from the buttons", &["input"]));
+
+// Chromium and ChromeDriver are started anew for every meeting
+pub static PROCESS_STARTS: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_process_starts_total", "Child processes started", &["process"]));
+
+// By result: "up_to_date", "available" or "failure"
+pub static UPDATE_CHECKS: LazyLock<IntCounterVec> =
+    LazyLock::new(|| counter_vec("rctv_update_checks_total", "Checks for a new version", &["result"]));
+
+pub static UPDATE_INSTALL_FAILURES: LazyLock<IntCounter> =
+    LazyLock::new(|| counter("rctv_update_install_failures_total", "Updates that could not be downloaded or installed"));
+
+// Everything in the Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        warn!(target: "api", "Could not encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
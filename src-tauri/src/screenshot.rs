@@ -0,0 +1,117 @@
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use tracing::{debug, info, warn};
+
+use crate::config::ScreenshotConfig;
+use crate::controls::Controller;
+use crate::util::unix_time;
+use crate::AutomationState;
+
+// The kiosk window's title, from tauri.conf.json
+const KIOSK_WINDOW: &str = "RCTV Kiosk";
+
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Screenshot {
+    pub jpeg: Vec<u8>,
+    pub taken_at: u64, // Unix time
+}
+
+// The last screenshot taken, served by the control API. Cleared while in a meeting, unless
+// `screenshots.in_meetings` is set.
+static LATEST: Mutex<Option<Screenshot>> = Mutex::new(None);
+
+pub fn with_latest<T>(f: impl FnOnce(Option<&Screenshot>) -> T) -> T {
+    f(LATEST.lock().unwrap().as_ref())
+}
+
+// ImageMagick's `import` grabs the window from X, scales it down and encodes it in one go.
+// The kiosk window in kiosk mode, the whole display otherwise (Chromium or the meeting window).
+fn capture(config: &ScreenshotConfig, state: AutomationState) -> Result<Vec<u8>, String> {
+    let window = if state == AutomationState::KioskMode { KIOSK_WINDOW } else { "root" };
+    let output = Command::new("import")
+        .args(["-silent", "-window", window])
+        .args(["-resize", &format!("{}x{}>", config.max_width, config.max_height)])
+        .args(["-quality", &config.quality.to_string()])
+        .arg("jpeg:-")
+        .output()
+        .map_err(|e| format!("could not run import (is imagemagick installed?): {}", e))?;
+    if !output.status.success() {
+        return Err(format!("import failed ({}): {}", output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    if output.stdout.is_empty() {
+        return Err("import gave an empty image".to_string());
+    }
+    Ok(output.stdout)
+}
+
+async fn upload(client: &reqwest::Client, url: &str, token: &str, jpeg: Vec<u8>) -> Result<(), String> {
+    let response = client
+        .post(url)
+        .query(&[("tv_login_token", token)])
+        .header(CONTENT_TYPE, "image/jpeg")
+        .body(jpeg)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("server answered {}", response.status()));
+    }
+    Ok(())
+}
+
+// Takes a screenshot every `screenshots.interval_seconds`, keeps it for the control API, and
+// posts it to `screenshots.upload_url` if set. Each different error is logged once.
+pub async fn start_screenshots(controller: Controller, token: String) {
+    let config = controller.config.screenshots.clone();
+    let client = reqwest::Client::builder().timeout(UPLOAD_TIMEOUT).build().unwrap_or_default();
+    info!(target: "process", "Taking screenshots every {} seconds", config.interval_seconds);
+
+    let mut last_error = None;
+    let mut interval = tokio::time::interval(config.interval());
+    loop {
+        interval.tick().await;
+
+        let state = *controller.state.lock().unwrap();
+        if state != AutomationState::KioskMode && !config.in_meetings {
+            if LATEST.lock().unwrap().take().is_some() {
+                debug!(target: "process", "No screenshots during the meeting");
+            }
+            continue;
+        }
+
+        let capture_config = config.clone();
+        let result = match tokio::task::spawn_blocking(move || capture(&capture_config, state)).await {
+            Ok(Ok(jpeg)) => {
+                // The meeting may have started while capturing
+                if *controller.state.lock().unwrap() != AutomationState::KioskMode && !config.in_meetings {
+                    continue;
+                }
+                *LATEST.lock().unwrap() = Some(Screenshot { jpeg: jpeg.clone(), taken_at: unix_time() });
+                match &config.upload_url {
+                    Some(url) => upload(&client, url, &token, jpeg).await.map_err(|e| format!("Could not upload screenshot: {}", e)),
+                    None => Ok(()),
+                }
+            }
+            Ok(Err(e)) => Err(format!("Could not take screenshot: {}", e)),
+            Err(e) => Err(format!("Could not take screenshot: {}", e)),
+        };
+
+        match result {
+            Ok(()) => {
+                if last_error.take().is_some() {
+                    info!(target: "process", "Screenshots working again");
+                }
+            }
+            Err(message) => {
+                if last_error.as_ref() != Some(&message) {
+                    warn!(target: "process", "{}", message);
+                }
+                last_error = Some(message);
+            }
+        }
+    }
+}
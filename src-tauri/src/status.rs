@@ -0,0 +1,251 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::config::{Config, TOKEN_PATH};
+use crate::controls::Controller;
+use crate::input::HidConnection;
+use crate::meeting::process_ids;
+use crate::AutomationState;
+
+// What the kiosk loop is up to, kept up to date by the loop itself. Managed as Tauri state.
+pub struct AppStatus {
+    started: Instant,
+    kiosk: Mutex<KioskInfo>,
+    updates: Mutex<UpdateInfo>,
+}
+
+#[derive(Default)]
+struct KioskInfo {
+    current_url: Option<String>,
+    shown_until: Option<Instant>,
+    playlist_size: usize,
+    playlist_fetched: Option<Instant>, // Last successful fetch
+    last_fetch: Option<(Instant, Option<String>)>, // When, and the error if it failed
+}
+
+#[derive(Default)]
+struct UpdateInfo {
+    last_check: Option<(Instant, Result<Option<String>, String>)>, // When, and the version found or the error
+    waiting_to_install: Option<String>, // Found during a meeting
+    install_error: Option<String>,
+}
+
+impl Default for AppStatus {
+    fn default() -> Self {
+        AppStatus {
+            started: Instant::now(),
+            kiosk: Mutex::new(KioskInfo::default()),
+            updates: Mutex::new(UpdateInfo::default()),
+        }
+    }
+}
+
+impl AppStatus {
+    pub fn playlist_fetched(&self, size: usize) {
+        let mut kiosk = self.kiosk.lock().unwrap();
+        let now = Instant::now();
+        kiosk.playlist_size = size;
+        kiosk.playlist_fetched = Some(now);
+        kiosk.last_fetch = Some((now, None));
+    }
+
+    // The previous playlist is kept, and so is its age
+    pub fn playlist_fetch_failed(&self, error: String) {
+        self.kiosk.lock().unwrap().last_fetch = Some((Instant::now(), Some(error)));
+    }
+
+    pub fn showing(&self, url: &str, duration: Duration) {
+        let mut kiosk = self.kiosk.lock().unwrap();
+        kiosk.current_url = Some(url.to_string());
+        kiosk.shown_until = Instant::now().checked_add(duration);
+    }
+
+    pub fn update_checked(&self, result: Result<Option<String>, String>) {
+        self.updates.lock().unwrap().last_check = Some((Instant::now(), result));
+    }
+
+    pub fn update_waiting(&self, version: Option<String>) {
+        self.updates.lock().unwrap().waiting_to_install = version;
+    }
+
+    pub fn update_install_failed(&self, error: String) {
+        let mut updates = self.updates.lock().unwrap();
+        updates.waiting_to_install = None;
+        updates.install_error = Some(error);
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatusReport {
+    version: &'static str,
+    uptime_seconds: u64,
+    state: AutomationState,
+    kiosk: KioskReport,
+    hid: HidConnection,
+    processes: ProcessReport,
+    updates: UpdateReport,
+}
+
+#[derive(Serialize)]
+struct KioskReport {
+    current_url: Option<String>, // Only while in kiosk mode
+    seconds_remaining: Option<u64>,
+    playlist_size: usize,
+    playlist_age_seconds: Option<u64>,
+    last_fetch: Option<FetchReport>,
+}
+
+#[derive(Serialize)]
+struct FetchReport {
+    ok: bool,
+    seconds_ago: u64,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UpdateReport {
+    last_check: Option<UpdateCheckReport>,
+    waiting_to_install: Option<String>, // Until the meeting is over
+    install_error: Option<String>,      // From the last attempt
+}
+
+#[derive(Serialize)]
+struct UpdateCheckReport {
+    ok: bool,
+    seconds_ago: u64,
+    available: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ProcessReport {
+    chromium: Vec<u32>,
+    chromedriver: Vec<u32>,
+}
+
+impl StatusReport {
+    pub async fn collect(controller: &Controller) -> StatusReport {
+        let processes = ProcessReport {
+            // Only the browser process we start has the debugging port, not its renderers
+            chromium: process_ids("--remote-debugging-port").await,
+            chromedriver: process_ids("chromedriver").await,
+        };
+        let app_status = controller.app_handle.state::<AppStatus>();
+        let state = *controller.state.lock().unwrap();
+        let now = Instant::now();
+        let kiosk = app_status.kiosk.lock().unwrap();
+        let updates = app_status.updates.lock().unwrap();
+        let in_kiosk = state == AutomationState::KioskMode;
+
+        StatusReport {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_seconds: app_status.started.elapsed().as_secs(),
+            state,
+            kiosk: KioskReport {
+                current_url: kiosk.current_url.clone().filter(|_| in_kiosk),
+                seconds_remaining: kiosk.shown_until.filter(|_| in_kiosk).map(|until| until.saturating_duration_since(now).as_secs()),
+                playlist_size: kiosk.playlist_size,
+                playlist_age_seconds: kiosk.playlist_fetched.map(|fetched| now.duration_since(fetched).as_secs()),
+                last_fetch: kiosk.last_fetch.as_ref().map(|(at, error)| FetchReport {
+                    ok: error.is_none(),
+                    seconds_ago: now.duration_since(*at).as_secs(),
+                    error: error.clone(),
+                }),
+            },
+            hid: controller.hid_connection.lock().unwrap().clone(),
+            processes,
+            updates: UpdateReport {
+                last_check: updates.last_check.as_ref().map(|(at, result)| UpdateCheckReport {
+                    ok: result.is_ok(),
+                    seconds_ago: now.duration_since(*at).as_secs(),
+                    available: result.clone().ok().flatten(),
+                    error: result.clone().err(),
+                }),
+                waiting_to_install: updates.waiting_to_install.clone(),
+                install_error: updates.install_error.clone(),
+            },
+        }
+    }
+}
+
+// Where to reach the control API from this machine, when it listens on every interface
+fn local_address(bind: &str) -> Option<SocketAddr> {
+    let mut address: SocketAddr = bind.parse().ok()?;
+    if address.ip().is_unspecified() {
+        address.set_ip(match address.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+    Some(address)
+}
+
+// `--status VALUE`, `--status=VALUE`, as tauri-plugin-cli would take them
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix(flag.as_str()) {
+        Some("") => args.get(i + 1).cloned(),
+        Some(rest) => rest.strip_prefix('=').map(str::to_string),
+        None => None,
+    })
+}
+
+// `--status`: asks the running instance through the control API and prints its report, with
+// the same --config and --token as the app. Returns the exit code.
+pub fn status_command(args: &[String]) -> i32 {
+    let config = match Config::load(arg_value(args, "config").as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    if !config.control_api.enabled {
+        eprintln!("Error: the control API is disabled (`control_api.enabled`), so there is nothing to ask");
+        return 1;
+    }
+    let Some(address) = local_address(&config.control_api.bind) else {
+        eprintln!("Error: invalid `control_api.bind` {}", config.control_api.bind);
+        return 1;
+    };
+    let token = config
+        .control_api
+        .token
+        .clone()
+        .or_else(|| arg_value(args, "token"))
+        .or_else(|| std::fs::read_to_string(TOKEN_PATH).ok().map(|token| token.trim().to_string()));
+    let Some(token) = token else {
+        eprintln!("Error: --token argument is required or {} file must exist", TOKEN_PATH);
+        return 1;
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let result = rt.block_on(async {
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/status", address))
+            .bearer_auth(token)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| format!("could not reach the running app at {}: {}", address, e))?;
+        if !response.status().is_success() {
+            return Err(format!("the running app answered {}", response.status()));
+        }
+        response.json::<serde_json::Value>().await.map_err(|e| e.to_string())
+    });
+
+    match result {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
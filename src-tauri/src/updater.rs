@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use tauri::Manager;
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tracing::{info, warn};
+
+use crate::controls::Controller;
+use crate::metrics;
+use crate::status::AppStatus;
+use crate::AutomationState;
+
+// How often to look again while an update waits for a meeting to end
+const INSTALL_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn check(controller: &Controller) -> Result<Option<Update>, String> {
+    let updater = controller.app_handle.updater().map_err(|e| e.to_string())?;
+    updater.check().await.map_err(|e| e.to_string())
+}
+
+// Whether the running bundle can be replaced by an update. The plugin only knows how to do that
+// for an AppImage on Linux, not e.g. the raw binary build-on-pi.sh copies to /opt/rctv-kiosk.
+fn can_install() -> Result<(), String> {
+    match std::env::var_os("APPIMAGE") {
+        Some(_) => Ok(()),
+        None => Err("not running from an AppImage".to_string()),
+    }
+}
+
+// Waits for kiosk mode, so nothing is downloaded or restarted during a meeting. `next` is what
+// happens to the update then, for the logs.
+async fn wait_for_kiosk_mode(controller: &Controller, update: &Update, next: &str) {
+    let mut waiting = false;
+    while *controller.state.lock().unwrap() != AutomationState::KioskMode {
+        if !waiting {
+            info!(target: "process", "Version {} will be {} after the meeting", update.version, next);
+            controller.app_handle.state::<AppStatus>().update_waiting(Some(update.version.clone()));
+            waiting = true;
+        }
+        tokio::time::sleep(INSTALL_RETRY_INTERVAL).await;
+    }
+}
+
+// Downloads and installs the update in kiosk mode, then restarts into it, never during a meeting.
+// Slides keep going while installing. Only returns if that failed.
+async fn install(controller: &Controller, update: &Update) -> Result<(), String> {
+    wait_for_kiosk_mode(controller, update, "installed").await;
+    // The plugin checks the signature against the pubkey in tauri.conf.json, and fails the
+    // download if it doesn't match
+    info!(target: "process", "Downloading version {}", update.version);
+    let bytes = update.download(|_, _| {}, || {}).await.map_err(|e| format!("download failed: {}", e))?;
+    update.install(bytes).map_err(|e| format!("install failed: {}", e))?;
+    info!(target: "process", "Installed version {}", update.version);
+
+    // A meeting may have started in the meantime. Buttons are ignored once the state is
+    // Updating, so none can start between checking and restarting.
+    loop {
+        wait_for_kiosk_mode(controller, update, "started").await;
+        let mut state = controller.state.lock().unwrap();
+        if *state == AutomationState::KioskMode {
+            *state = AutomationState::Updating;
+            break;
+        }
+    }
+    info!(target: "process", "Restarting into version {}", update.version);
+    controller.app_handle.restart();
+}
+
+// Checks for updates `updater.first_check_minutes` after startup, then every
+// `updater.interval_hours`. Results go in the logs, the status report and the metrics.
+pub async fn start_updater(controller: Controller) {
+    let config = controller.config.updater.clone();
+    let app_status = controller.app_handle.state::<AppStatus>();
+    info!(target: "process", "Running version {}, checking for updates every {} hours", env!("CARGO_PKG_VERSION"), config.interval_hours);
+    let install_updates = config.install
+        && can_install()
+            .map_err(|reason| warn!(target: "process", "Updates can't be installed ({}), they will only be reported", reason))
+            .is_ok();
+
+    tokio::time::sleep(config.first_check()).await;
+    loop {
+        match check(&controller).await {
+            Ok(None) => {
+                info!(target: "process", "Up to date");
+                metrics::UPDATE_CHECKS.with_label_values(&["up_to_date"]).inc();
+                app_status.update_checked(Ok(None));
+            }
+            Ok(Some(update)) => {
+                info!(target: "process", "Version {} is available, running {}", update.version, update.current_version);
+                metrics::UPDATE_CHECKS.with_label_values(&["available"]).inc();
+                app_status.update_checked(Ok(Some(update.version.clone())));
+                if install_updates {
+                    if let Err(e) = install(&controller, &update).await {
+                        warn!(target: "process", "Could not update to version {}: {}", update.version, e);
+                        metrics::UPDATE_INSTALL_FAILURES.inc();
+                        app_status.update_install_failed(e);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!(target: "process", "Could not check for updates: {}", e);
+                metrics::UPDATE_CHECKS.with_label_values(&["failure"]).inc();
+                app_status.update_checked(Err(e));
+            }
+        }
+        tokio::time::sleep(config.interval()).await;
+    }
+}